@@ -37,6 +37,16 @@ export function tierToNumber(tier: Tier): number {
     return tierMap[tier];
 }
 
+/**
+ * Inverse of tierToNumber
+ */
+export function numberToTier(value: number): Tier {
+    const tiers: Tier[] = ["BRONZE_TIER", "SILVER_TIER", "GOLD_TIER", "PLATINUM_TIER"];
+    const tier = tiers[value];
+    if (!tier) throw new Error(`Unknown tier value ${value}`);
+    return tier;
+}
+
 export class ArciumHander {
     private cipher: RescueCipher | null = null;
     private clientPubKey: Uint8Array | null = null;
@@ -138,4 +148,42 @@ export class ArciumHander {
             fieldCount: 6
         };
     }
+
+    /**
+     * Key and fresh nonce to pass to read_my_profile.
+     * The vault re-encrypts the stored profile for this session's key, so the
+     * ProfileReadBack event can be decrypted with decryptInterestProfile()
+     */
+    prepareProfileReadBack() {
+        if (!this.cipher) throw new Error("Encryption not initialized");
+
+        const nonce = crypto.getRandomValues(new Uint8Array(16));
+        return {
+            nonce,
+            clientPubKey: this.clientPubKey
+        };
+    }
+
+    /**
+     * Decrypts a profile re-encrypted for this session (ProfileReadBack event)
+     * Converts lamports and cents back to the units of InterestProfile
+     *
+     * @param ciphertexts - The 6 ciphertexts from the event, in InterestProfileTuple order
+     * @param nonce - The nonce from the event (16 bytes, little-endian)
+     */
+    decryptInterestProfile(ciphertexts: number[][], nonce: Uint8Array): InterestProfile {
+        if (!this.cipher) throw new Error("Encryption not initialized");
+
+        const [tier, nftCount, solBalanceLamports, tradingVolumeCents, tokenHoldings, defiInteractions] =
+            this.cipher.decrypt(ciphertexts, nonce);
+
+        return {
+            tier: numberToTier(Number(tier)),
+            nftCount: Number(nftCount),
+            solBalance: Number(solBalanceLamports) / 1_000_000_000,
+            tradingVolume: Number(tradingVolumeCents) / 100,
+            tokenHoldings: Number(tokenHoldings),
+            defiInteractions: Number(defiInteractions)
+        };
+    }
 }
\ No newline at end of file
@@ -277,6 +277,8 @@ export async function categorizeWallet(address: string): Promise<InterestProfile
     }
     
     // 6. Determine tier based on comprehensive metrics
+    // This is only a local preview: the vault re-derives the tier inside MPC from the
+    // on-chain TierPolicy (see iap_vault init_tier_policy / update_tier_policy).
     // PLATINUM: >100 NFTs AND >100 SOL (mega whales)
     // GOLD: >49 NFTs AND >30 SOL (whales)
     // SILVER: >20 NFTs AND >10 SOL (active users)
@@ -4,15 +4,588 @@ use arcis::*;
 mod circuits {
     use arcis::*;
 
-    pub struct InputValues {
-        v1: u8,
-        v2: u8,
+    // Tier values, matching tierToNumber in arcium_sdk
+    // BRONZE = 0, SILVER = 1, GOLD = 2, PLATINUM = 3
+    pub const BRONZE_TIER: u8 = 0;
+    pub const SILVER_TIER: u8 = 1;
+    pub const GOLD_TIER: u8 = 2;
+    pub const PLATINUM_TIER: u8 = 3;
+
+    // Cap on each geometric noise sample. Must match MAX_NOISE_TRIALS in iap_vault,
+    // which keeps epsilon high enough for the cap to be reached only negligibly often.
+    pub const MAX_NOISE_TRIALS: usize = 64;
+
+    // Inputs of median_interest_profile. Must match MAX_QUORUM_SUBMISSIONS in iap_vault.
+    pub const MAX_QUORUM_SUBMISSIONS: usize = 5;
+
+    // Per-tier thresholds, supplied as plaintext from the on-chain TierPolicy.
+    // A wallet reaches a tier when it is strictly above the NFT and SOL thresholds
+    // and at least at the trading volume and DeFi minimums.
+    pub struct TierThresholds {
+        nft_count_above: u32,
+        sol_lamports_above: u64,
+        min_trading_volume_cents: u64,
+        min_defi_interactions: u32,
+    }
+
+    // Interest Profile structure matching TypeScript InterestProfile
+    // Field order must match InterestProfileTuple in the iap_vault program:
+    // tier, nftCount, solBalanceLamports, tradingVolumeCents, tokenHoldings, defiInteractions
+    pub struct InterestProfile {
+        tier: u8,
+        nft_count: u32,
+        sol_balance_lamports: u64,
+        trading_volume_cents: u64,
+        token_holdings: u32,
+        defi_interactions: u32,
+    }
+
+    // Re-encrypts a profile submitted by the user (shared secret with the MXE)
+    // so that only the MXE can decrypt it from now on.
+    // The client-supplied tier is never trusted: it is re-derived from the
+    // encrypted metrics under the given tier policy before storing.
+    // The policy version is passed through so the callback records exactly
+    // which policy the stored tier was computed under.
+    #[instruction]
+    pub fn store_interest_profile(
+        input_ctxt: Enc<Shared, InterestProfile>,
+        policy_version: u32,
+        silver: TierThresholds,
+        gold: TierThresholds,
+        platinum: TierThresholds,
+    ) -> (Enc<Mxe, InterestProfile>, u32) {
+        let profile = with_derived_tier(input_ctxt.to_arcis(), silver, gold, platinum);
+        (Mxe::get().from_arcis(profile), policy_version)
+    }
+
+    // Recomputes the tier of an already stored profile and overwrites field 0.
+    #[instruction]
+    pub fn compute_tier(
+        profile_ctxt: Enc<Mxe, InterestProfile>,
+        policy_version: u32,
+        silver: TierThresholds,
+        gold: TierThresholds,
+        platinum: TierThresholds,
+    ) -> (Enc<Mxe, InterestProfile>, u32) {
+        let profile = with_derived_tier(profile_ctxt.to_arcis(), silver, gold, platinum);
+        (profile_ctxt.owner.from_arcis(profile), policy_version)
+    }
+
+    // Combines the profiles an oracle quorum submitted for one wallet into their
+    // per-field median, so no single oracle can inflate or deflate a metric. Only the
+    // first `count` submissions are used (1 to MAX_QUORUM_SUBMISSIONS, the round's
+    // submissions in iap_vault); the remaining inputs are ignored. The submitted tiers
+    // are ignored too: the tier is derived from the median metrics, as in
+    // store_interest_profile.
+    #[instruction]
+    pub fn median_interest_profile(
+        submission_0: Enc<Shared, InterestProfile>,
+        submission_1: Enc<Shared, InterestProfile>,
+        submission_2: Enc<Shared, InterestProfile>,
+        submission_3: Enc<Shared, InterestProfile>,
+        submission_4: Enc<Shared, InterestProfile>,
+        count: u8,
+        policy_version: u32,
+        silver: TierThresholds,
+        gold: TierThresholds,
+        platinum: TierThresholds,
+    ) -> (Enc<Mxe, InterestProfile>, u32) {
+        let a = submission_0.to_arcis();
+        let b = submission_1.to_arcis();
+        let c = submission_2.to_arcis();
+        let d = submission_3.to_arcis();
+        let e = submission_4.to_arcis();
+        let median = InterestProfile {
+            tier: BRONZE_TIER,
+            nft_count: median_u32(
+                [a.nft_count, b.nft_count, c.nft_count, d.nft_count, e.nft_count],
+                count,
+            ),
+            sol_balance_lamports: median_u64(
+                [
+                    a.sol_balance_lamports,
+                    b.sol_balance_lamports,
+                    c.sol_balance_lamports,
+                    d.sol_balance_lamports,
+                    e.sol_balance_lamports,
+                ],
+                count,
+            ),
+            trading_volume_cents: median_u64(
+                [
+                    a.trading_volume_cents,
+                    b.trading_volume_cents,
+                    c.trading_volume_cents,
+                    d.trading_volume_cents,
+                    e.trading_volume_cents,
+                ],
+                count,
+            ),
+            token_holdings: median_u32(
+                [
+                    a.token_holdings,
+                    b.token_holdings,
+                    c.token_holdings,
+                    d.token_holdings,
+                    e.token_holdings,
+                ],
+                count,
+            ),
+            defi_interactions: median_u32(
+                [
+                    a.defi_interactions,
+                    b.defi_interactions,
+                    c.defi_interactions,
+                    d.defi_interactions,
+                    e.defi_interactions,
+                ],
+                count,
+            ),
+        };
+        let profile = with_derived_tier(median, silver, gold, platinum);
+        (Mxe::get().from_arcis(profile), policy_version)
+    }
+
+    // Plaintext advertiser criteria. Each field is a minimum the profile must reach.
+    pub struct EligibilityCriteria {
+        min_tier: u8,
+        min_nft_count: u32,
+        min_sol_lamports: u64,
+        min_trading_volume_cents: u64,
+        min_token_holdings: u32,
+        min_defi_interactions: u32,
+    }
+
+    // Re-encrypts the stored profile for its owner under a fresh x25519 key, so they
+    // can read back exactly what the vault holds.
+    #[instruction]
+    pub fn read_my_profile(
+        owner: Shared,
+        profile_ctxt: Enc<Mxe, InterestProfile>,
+    ) -> Enc<Shared, InterestProfile> {
+        let profile = profile_ctxt.to_arcis();
+        owner.from_arcis(profile)
+    }
+
+    // Re-encrypts a stored profile for a third party (`recipient`), keeping only the
+    // fields selected by `field_mask` (bit i = field i, in InterestProfile order) and
+    // zeroing the others. The mask is passed through so the callback can report it.
+    #[instruction]
+    pub fn disclose_fields(
+        recipient: Shared,
+        profile_ctxt: Enc<Mxe, InterestProfile>,
+        field_mask: u8,
+    ) -> (Enc<Shared, InterestProfile>, u8) {
+        let profile = profile_ctxt.to_arcis();
+        let disclosed = InterestProfile {
+            tier: keep_u8(profile.tier, field_mask & 1 != 0),
+            nft_count: keep_u32(profile.nft_count, field_mask & 2 != 0),
+            sol_balance_lamports: keep_u64(profile.sol_balance_lamports, field_mask & 4 != 0),
+            trading_volume_cents: keep_u64(profile.trading_volume_cents, field_mask & 8 != 0),
+            token_holdings: keep_u32(profile.token_holdings, field_mask & 16 != 0),
+            defi_interactions: keep_u32(profile.defi_interactions, field_mask & 32 != 0),
+        };
+        (recipient.from_arcis(disclosed), field_mask)
+    }
+
+    // Compares a stored profile against the criteria and reveals only the yes/no answer.
+    #[instruction]
+    pub fn check_eligibility(
+        profile_ctxt: Enc<Mxe, InterestProfile>,
+        criteria: EligibilityCriteria,
+    ) -> bool {
+        let profile = profile_ctxt.to_arcis();
+        meets_criteria(profile, criteria).reveal()
+    }
+
+    // Advertiser criteria kept private: same minimums as EligibilityCriteria, but
+    // submitted encrypted and stored as Enc<Mxe, CampaignCriteria> so the targeting
+    // strategy is never public.
+    pub struct CampaignCriteria {
+        min_tier: u8,
+        min_nft_count: u32,
+        min_sol_lamports: u64,
+        min_trading_volume_cents: u64,
+        min_token_holdings: u32,
+        min_defi_interactions: u32,
+    }
+
+    #[instruction]
+    pub fn store_campaign_criteria(
+        input_ctxt: Enc<Shared, CampaignCriteria>,
+    ) -> Enc<Mxe, CampaignCriteria> {
+        let criteria = input_ctxt.to_arcis();
+        Mxe::get().from_arcis(criteria)
+    }
+
+    // Compares two MXE-encrypted structs: neither the profile nor the campaign
+    // rules are decrypted outside MPC, only the match result is revealed.
+    #[instruction]
+    pub fn match_campaign_criteria(
+        profile_ctxt: Enc<Mxe, InterestProfile>,
+        criteria_ctxt: Enc<Mxe, CampaignCriteria>,
+    ) -> bool {
+        let profile = profile_ctxt.to_arcis();
+        let criteria = criteria_ctxt.to_arcis();
+        meets_criteria(profile, as_eligibility_criteria(criteria)).reveal()
+    }
+
+    // Eligibility check behind campaign reward claims. Same comparison as
+    // match_campaign_criteria, kept as its own circuit so the claim callback can
+    // settle the payout.
+    #[instruction]
+    pub fn claim_campaign_reward(
+        profile_ctxt: Enc<Mxe, InterestProfile>,
+        criteria_ctxt: Enc<Mxe, CampaignCriteria>,
+    ) -> bool {
+        let profile = profile_ctxt.to_arcis();
+        let criteria = criteria_ctxt.to_arcis();
+        meets_criteria(profile, as_eligibility_criteria(criteria)).reveal()
+    }
+
+    // Adds the number of matching profiles in a batch of PROFILE_BATCH_SIZE to an
+    // encrypted running count. Bit i of `included` marks slot i as a real profile;
+    // unused slots are padding and never counted. The running count is ignored on
+    // the first batch (profiles_scanned == 0), when the account holds no ciphertext yet.
+    #[instruction]
+    pub fn estimate_audience(
+        criteria_ctxt: Enc<Mxe, CampaignCriteria>,
+        running_ctxt: Enc<Mxe, u32>,
+        profiles_scanned: u32,
+        included: u8,
+        profile_0: Enc<Mxe, InterestProfile>,
+        profile_1: Enc<Mxe, InterestProfile>,
+        profile_2: Enc<Mxe, InterestProfile>,
+        profile_3: Enc<Mxe, InterestProfile>,
+    ) -> Enc<Mxe, u32> {
+        let criteria = as_eligibility_criteria(criteria_ctxt.to_arcis());
+        let running = running_ctxt.to_arcis();
+
+        let mut count = 0;
+        if profiles_scanned > 0 {
+            count = running;
+        }
+        count = saturating_add_u32(count, counted_match(profile_0.to_arcis(), criteria, included & 1 != 0));
+        count = saturating_add_u32(count, counted_match(profile_1.to_arcis(), criteria, included & 2 != 0));
+        count = saturating_add_u32(count, counted_match(profile_2.to_arcis(), criteria, included & 4 != 0));
+        count = saturating_add_u32(count, counted_match(profile_3.to_arcis(), criteria, included & 8 != 0));
+
+        Mxe::get().from_arcis(count)
+    }
+
+    // Reveals a noisy audience count (see add_geometric_noise) only if it reaches the
+    // k-anonymity floor. Below the floor (false, 0) is revealed instead.
+    #[instruction]
+    pub fn reveal_audience_estimate(
+        running_ctxt: Enc<Mxe, u32>,
+        k_anonymity_floor: u32,
+        noise_threshold: u64,
+    ) -> (bool, u32) {
+        let count = add_geometric_noise(running_ctxt.to_arcis(), noise_threshold);
+        let meets_floor = at_least_u32(count, k_anonymity_floor);
+        let audience_size = suppress_below(count, k_anonymity_floor);
+        (meets_floor.reveal(), audience_size.reveal())
+    }
+
+    // Users per tier across the vault, indexed like the *_TIER constants
+    pub struct TierHistogram {
+        bronze: u32,
+        silver: u32,
+        gold: u32,
+        platinum: u32,
+    }
+
+    // Folds a batch of PROFILE_BATCH_SIZE profiles into the encrypted running
+    // histogram. `profiles_scanned` and `included` work as in estimate_audience.
+    #[instruction]
+    pub fn aggregate_tier_histogram(
+        running_ctxt: Enc<Mxe, TierHistogram>,
+        profiles_scanned: u32,
+        included: u8,
+        profile_0: Enc<Mxe, InterestProfile>,
+        profile_1: Enc<Mxe, InterestProfile>,
+        profile_2: Enc<Mxe, InterestProfile>,
+        profile_3: Enc<Mxe, InterestProfile>,
+    ) -> Enc<Mxe, TierHistogram> {
+        let running = running_ctxt.to_arcis();
+
+        let mut histogram = TierHistogram {
+            bronze: 0,
+            silver: 0,
+            gold: 0,
+            platinum: 0,
+        };
+        if profiles_scanned > 0 {
+            histogram = running;
+        }
+        histogram = add_to_histogram(histogram, profile_0.to_arcis(), included & 1 != 0);
+        histogram = add_to_histogram(histogram, profile_1.to_arcis(), included & 2 != 0);
+        histogram = add_to_histogram(histogram, profile_2.to_arcis(), included & 4 != 0);
+        histogram = add_to_histogram(histogram, profile_3.to_arcis(), included & 8 != 0);
+
+        Mxe::get().from_arcis(histogram)
     }
 
+    // Reveals the histogram with independent noise on every bucket, and every noisy
+    // bucket below the k-anonymity floor shown as 0. Adding or removing one profile
+    // changes a single bucket by 1, so the whole reveal costs one epsilon.
     #[instruction]
-    pub fn add_together(input_ctxt: Enc<Shared, InputValues>) -> Enc<Shared, u16> {
-        let input = input_ctxt.to_arcis();
-        let sum = input.v1 as u16 + input.v2 as u16;
-        input_ctxt.owner.from_arcis(sum)
+    pub fn reveal_tier_histogram(
+        running_ctxt: Enc<Mxe, TierHistogram>,
+        k_anonymity_floor: u32,
+        noise_threshold: u64,
+    ) -> (u32, u32, u32, u32) {
+        let histogram = running_ctxt.to_arcis();
+        let bronze = add_geometric_noise(histogram.bronze, noise_threshold);
+        let silver = add_geometric_noise(histogram.silver, noise_threshold);
+        let gold = add_geometric_noise(histogram.gold, noise_threshold);
+        let platinum = add_geometric_noise(histogram.platinum, noise_threshold);
+        (
+            suppress_below(bronze, k_anonymity_floor).reveal(),
+            suppress_below(silver, k_anonymity_floor).reveal(),
+            suppress_below(gold, k_anonymity_floor).reveal(),
+            suppress_below(platinum, k_anonymity_floor).reveal(),
+        )
+    }
+
+    // ---------------------------------------------------------------------
+    // Shared helpers
+    //
+    // Every profile circuit goes through these instead of handling fields
+    // itself. Both branches of an `if` on secret data are evaluated inside
+    // MPC, so all helpers are branch-free in cost and safe on Enc data.
+    // ---------------------------------------------------------------------
+
+    pub fn with_derived_tier(
+        profile: InterestProfile,
+        silver: TierThresholds,
+        gold: TierThresholds,
+        platinum: TierThresholds,
+    ) -> InterestProfile {
+        let tier = derive_tier(
+            profile.nft_count,
+            profile.sol_balance_lamports,
+            profile.trading_volume_cents,
+            profile.defi_interactions,
+            silver,
+            gold,
+            platinum,
+        );
+        InterestProfile {
+            tier,
+            nft_count: profile.nft_count,
+            sol_balance_lamports: profile.sol_balance_lamports,
+            trading_volume_cents: profile.trading_volume_cents,
+            token_holdings: profile.token_holdings,
+            defi_interactions: profile.defi_interactions,
+        }
+    }
+
+    // Tier derivation: the highest tier whose thresholds are all met, BRONZE otherwise.
+    pub fn derive_tier(
+        nft_count: u32,
+        sol_balance_lamports: u64,
+        trading_volume_cents: u64,
+        defi_interactions: u32,
+        silver: TierThresholds,
+        gold: TierThresholds,
+        platinum: TierThresholds,
+    ) -> u8 {
+        let mut tier = BRONZE_TIER;
+        if reaches_tier(nft_count, sol_balance_lamports, trading_volume_cents, defi_interactions, silver) {
+            tier = SILVER_TIER;
+        }
+        if reaches_tier(nft_count, sol_balance_lamports, trading_volume_cents, defi_interactions, gold) {
+            tier = GOLD_TIER;
+        }
+        if reaches_tier(nft_count, sol_balance_lamports, trading_volume_cents, defi_interactions, platinum) {
+            tier = PLATINUM_TIER;
+        }
+        tier
+    }
+
+    pub fn reaches_tier(
+        nft_count: u32,
+        sol_balance_lamports: u64,
+        trading_volume_cents: u64,
+        defi_interactions: u32,
+        thresholds: TierThresholds,
+    ) -> bool {
+        nft_count > thresholds.nft_count_above
+            && sol_balance_lamports > thresholds.sol_lamports_above
+            && at_least_u64(trading_volume_cents, thresholds.min_trading_volume_cents)
+            && at_least_u32(defi_interactions, thresholds.min_defi_interactions)
+    }
+
+    pub fn meets_criteria(profile: InterestProfile, criteria: EligibilityCriteria) -> bool {
+        at_least_u8(profile.tier, criteria.min_tier)
+            && at_least_u32(profile.nft_count, criteria.min_nft_count)
+            && at_least_u64(profile.sol_balance_lamports, criteria.min_sol_lamports)
+            && at_least_u64(profile.trading_volume_cents, criteria.min_trading_volume_cents)
+            && at_least_u32(profile.token_holdings, criteria.min_token_holdings)
+            && at_least_u32(profile.defi_interactions, criteria.min_defi_interactions)
+    }
+
+    pub fn counted_match(profile: InterestProfile, criteria: EligibilityCriteria, included: bool) -> u32 {
+        one_if(included && meets_criteria(profile, criteria))
+    }
+
+    pub fn add_to_histogram(histogram: TierHistogram, profile: InterestProfile, included: bool) -> TierHistogram {
+        TierHistogram {
+            bronze: saturating_add_u32(histogram.bronze, one_if(included && profile.tier == BRONZE_TIER)),
+            silver: saturating_add_u32(histogram.silver, one_if(included && profile.tier == SILVER_TIER)),
+            gold: saturating_add_u32(histogram.gold, one_if(included && profile.tier == GOLD_TIER)),
+            platinum: saturating_add_u32(histogram.platinum, one_if(included && profile.tier == PLATINUM_TIER)),
+        }
+    }
+
+    // Differential privacy for sensitivity-1 counts: adds two-sided geometric noise
+    // (the discrete Laplace mechanism), i.e. the difference of two geometric samples
+    // whose continue probability is noise_threshold / 2^32 = exp(-epsilon). The
+    // threshold is computed on-chain from the privacy config. Every aggregate must
+    // go through this before it is revealed.
+    pub fn add_geometric_noise(count: u32, noise_threshold: u64) -> u32 {
+        let up = geometric_sample(noise_threshold);
+        let down = geometric_sample(noise_threshold);
+        saturating_sub_u32(saturating_add_u32(count, up), down)
+    }
+
+    // Number of consecutive draws below the threshold, capped at MAX_NOISE_TRIALS.
+    // The loop always runs to the cap so its cost does not depend on the sample.
+    pub fn geometric_sample(noise_threshold: u64) -> u32 {
+        let mut sample = 0;
+        let mut running = true;
+        for _ in 0..MAX_NOISE_TRIALS {
+            let draw = ArcisRNG::gen_integer_from_width(32) as u64;
+            running = running && draw < noise_threshold;
+            sample += one_if(running);
+        }
+        sample
+    }
+
+    // k-anonymity: counts below the floor are replaced by 0 before being revealed
+    pub fn suppress_below(count: u32, floor: u32) -> u32 {
+        let mut shown = 0;
+        if at_least_u32(count, floor) {
+            shown = count;
+        }
+        shown
+    }
+
+    pub fn one_if(condition: bool) -> u32 {
+        let mut one = 0;
+        if condition {
+            one = 1;
+        }
+        one
+    }
+
+    pub fn as_eligibility_criteria(criteria: CampaignCriteria) -> EligibilityCriteria {
+        EligibilityCriteria {
+            min_tier: criteria.min_tier,
+            min_nft_count: criteria.min_nft_count,
+            min_sol_lamports: criteria.min_sol_lamports,
+            min_trading_volume_cents: criteria.min_trading_volume_cents,
+            min_token_holdings: criteria.min_token_holdings,
+            min_defi_interactions: criteria.min_defi_interactions,
+        }
+    }
+
+    // Field masking for selective disclosure: the value if kept, 0 otherwise
+    pub fn keep_u8(value: u8, keep: bool) -> u8 {
+        let mut kept = 0;
+        if keep {
+            kept = value;
+        }
+        kept
+    }
+
+    pub fn keep_u32(value: u32, keep: bool) -> u32 {
+        let mut kept = 0;
+        if keep {
+            kept = value;
+        }
+        kept
+    }
+
+    pub fn keep_u64(value: u64, keep: bool) -> u64 {
+        let mut kept = 0;
+        if keep {
+            kept = value;
+        }
+        kept
+    }
+
+    // Threshold comparisons: "at least" semantics used by eligibility criteria
+    pub fn at_least_u8(value: u8, min: u8) -> bool {
+        value >= min
+    }
+
+    pub fn at_least_u32(value: u32, min: u32) -> bool {
+        value >= min
+    }
+
+    pub fn at_least_u64(value: u64, min: u64) -> bool {
+        value >= min
+    }
+
+    // Median of the first `count` values. The unused slots are padded alternately
+    // with 0 and the maximum, which keeps the median of an odd count and gives the
+    // lower median of an even one. The values are then sorted by a fixed network of
+    // compare-and-swaps, so the circuit does not depend on them.
+    pub fn median_u32(values: [u32; MAX_QUORUM_SUBMISSIONS], count: u8) -> u32 {
+        let mut sorted = values;
+        for i in 0..MAX_QUORUM_SUBMISSIONS {
+            if i as u8 >= count {
+                sorted[i] = if (i as u8 + count) % 2 == 0 { 0 } else { u32::MAX };
+            }
+        }
+        for pass in 0..MAX_QUORUM_SUBMISSIONS - 1 {
+            for j in 0..MAX_QUORUM_SUBMISSIONS - 1 - pass {
+                if sorted[j] > sorted[j + 1] {
+                    let low = sorted[j + 1];
+                    sorted[j + 1] = sorted[j];
+                    sorted[j] = low;
+                }
+            }
+        }
+        sorted[MAX_QUORUM_SUBMISSIONS / 2]
+    }
+
+    pub fn median_u64(values: [u64; MAX_QUORUM_SUBMISSIONS], count: u8) -> u64 {
+        let mut sorted = values;
+        for i in 0..MAX_QUORUM_SUBMISSIONS {
+            if i as u8 >= count {
+                sorted[i] = if (i as u8 + count) % 2 == 0 { 0 } else { u64::MAX };
+            }
+        }
+        for pass in 0..MAX_QUORUM_SUBMISSIONS - 1 {
+            for j in 0..MAX_QUORUM_SUBMISSIONS - 1 - pass {
+                if sorted[j] > sorted[j + 1] {
+                    let low = sorted[j + 1];
+                    sorted[j + 1] = sorted[j];
+                    sorted[j] = low;
+                }
+            }
+        }
+        sorted[MAX_QUORUM_SUBMISSIONS / 2]
+    }
+
+    // Saturating arithmetic: MPC integers wrap silently, so counters and
+    // aggregates must clamp instead of overflowing.
+    pub fn saturating_add_u32(a: u32, b: u32) -> u32 {
+        if a > u32::MAX - b {
+            u32::MAX
+        } else {
+            a + b
+        }
+    }
+
+    pub fn saturating_sub_u32(a: u32, b: u32) -> u32 {
+        if a > b {
+            a - b
+        } else {
+            0
+        }
     }
 }
@@ -0,0 +1,138 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { PublicKey } from "@solana/web3.js";
+import { IapVault } from "../target/types/iap_vault";
+import {
+  getCompDefAccOffset,
+  getArciumAccountBaseSeed,
+  getArciumProgramId,
+  uploadCircuit,
+  buildFinalizeCompDefTx,
+  getMXEAccAddress,
+} from "@arcium-hq/client";
+import * as fs from "fs";
+
+// Every circuit in encrypted-ixs that iap_vault queues. Each one has a matching
+// `init_<circuit>_comp_def` instruction in the program.
+export const VAULT_CIRCUITS = [
+  "store_interest_profile",
+  "compute_tier",
+  "check_eligibility",
+  "disclose_fields",
+  "read_my_profile",
+  "store_campaign_criteria",
+  "match_campaign_criteria",
+  "claim_campaign_reward",
+  "estimate_audience",
+  "reveal_audience_estimate",
+  "aggregate_tier_histogram",
+  "reveal_tier_histogram",
+  "median_interest_profile",
+] as const;
+
+export type VaultCircuit = (typeof VAULT_CIRCUITS)[number];
+
+export function getCompDefPda(
+  programId: PublicKey,
+  circuit: VaultCircuit,
+): PublicKey {
+  const baseSeedCompDefAcc = getArciumAccountBaseSeed(
+    "ComputationDefinitionAccount",
+  );
+  const offset = getCompDefAccOffset(circuit);
+
+  return PublicKey.findProgramAddressSync(
+    [baseSeedCompDefAcc, programId.toBuffer(), offset],
+    getArciumProgramId(),
+  )[0];
+}
+
+// store_interest_profile -> initStoreInterestProfileCompDef
+function initMethodName(circuit: VaultCircuit): string {
+  const pascal = circuit
+    .split("_")
+    .map((part) => part.charAt(0).toUpperCase() + part.slice(1))
+    .join("");
+  return `init${pascal}CompDef`;
+}
+
+export async function initCompDef(
+  provider: anchor.AnchorProvider,
+  program: Program<IapVault>,
+  owner: anchor.web3.Keypair,
+  circuit: VaultCircuit,
+  uploadRawCircuit: boolean,
+  offchainSource: boolean,
+): Promise<string> {
+  const compDefPDA = getCompDefPda(program.programId, circuit);
+  console.log(`Comp def pda for ${circuit} is `, compDefPDA.toBase58());
+
+  const sig = await (program.methods as any)
+    [initMethodName(circuit)]()
+    .accounts({
+      compDefAccount: compDefPDA,
+      payer: owner.publicKey,
+      mxeAccount: getMXEAccAddress(program.programId),
+    })
+    .signers([owner])
+    .rpc({
+      commitment: "confirmed",
+    });
+  console.log(`Init ${circuit} computation definition transaction`, sig);
+
+  if (uploadRawCircuit) {
+    const rawCircuit = fs.readFileSync(`build/${circuit}.arcis`);
+
+    await uploadCircuit(
+      provider,
+      circuit,
+      program.programId,
+      rawCircuit,
+      true,
+    );
+  } else if (!offchainSource) {
+    const offset = getCompDefAccOffset(circuit);
+    const finalizeTx = await buildFinalizeCompDefTx(
+      provider,
+      Buffer.from(offset).readUInt32LE(),
+      program.programId,
+    );
+
+    const latestBlockhash = await provider.connection.getLatestBlockhash();
+    finalizeTx.recentBlockhash = latestBlockhash.blockhash;
+    finalizeTx.lastValidBlockHeight = latestBlockhash.lastValidBlockHeight;
+
+    finalizeTx.sign(owner);
+
+    await provider.sendAndConfirm(finalizeTx);
+  }
+  return sig;
+}
+
+// Initializes every vault computation definition that does not exist yet, so a
+// fresh localnet/devnet deployment can be bootstrapped in one go.
+export async function initAllCompDefs(
+  provider: anchor.AnchorProvider,
+  program: Program<IapVault>,
+  owner: anchor.web3.Keypair,
+  uploadRawCircuit: boolean = false,
+  offchainSource: boolean = false,
+): Promise<void> {
+  for (const circuit of VAULT_CIRCUITS) {
+    const existing = await provider.connection.getAccountInfo(
+      getCompDefPda(program.programId, circuit),
+    );
+    if (existing) {
+      console.log(`Computation definition ${circuit} already initialized`);
+      continue;
+    }
+    await initCompDef(
+      provider,
+      program,
+      owner,
+      circuit,
+      uploadRawCircuit,
+      offchainSource,
+    );
+  }
+}
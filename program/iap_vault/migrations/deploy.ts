@@ -3,10 +3,53 @@
 // configured from the workspace's Anchor.toml.
 
 import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { PublicKey } from "@solana/web3.js";
+import { IapVault } from "../target/types/iap_vault";
+import { initAllCompDefs } from "./compDefs";
+import { addOracle, initOracleRegistry } from "./oracleRegistry";
+import { initPrivacyConfig, initVaultStats } from "./privacyConfig";
+import { initTierPolicy } from "./tierPolicy";
+import { initializeVault } from "./vaultConfig";
 
 module.exports = async function (provider: anchor.AnchorProvider) {
   // Configure client to use the provider.
   anchor.setProvider(provider);
 
-  // Add your deploy script here.
+  const program = anchor.workspace.IapVault as Program<IapVault>;
+  const owner = (provider.wallet as anchor.Wallet).payer;
+
+  // Bootstrap every computation definition the vault queues
+  await initAllCompDefs(provider, program, owner);
+
+  // Tier thresholds used by the tier-derivation circuits
+  await initTierPolicy(provider, program, owner);
+
+  // Admin, pause flags and fees; points at the tier policy created above
+  await initializeVault(provider, program, owner);
+
+  // Limits on what aggregate computations may reveal
+  await initPrivacyConfig(provider, program, owner);
+  await initVaultStats(provider, program, owner);
+
+  // Profiler oracles allowed to attest stored profiles. Only the keys listed in
+  // IAP_PROFILER_ORACLES (comma-separated) are registered; the deployer wallet is
+  // never trusted as an oracle by default.
+  await initOracleRegistry(provider, program, owner);
+  for (const oracle of profilerOracleKeys()) {
+    await addOracle(program, owner, oracle);
+  }
 };
+
+function profilerOracleKeys(): PublicKey[] {
+  const keys = (process.env.IAP_PROFILER_ORACLES ?? "")
+    .split(",")
+    .map((key) => key.trim())
+    .filter((key) => key.length > 0);
+  if (keys.length === 0) {
+    console.log(
+      "IAP_PROFILER_ORACLES is not set, no profiler oracle registered",
+    );
+  }
+  return keys.map((key) => new PublicKey(key));
+}
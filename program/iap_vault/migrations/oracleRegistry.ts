@@ -0,0 +1,117 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import {
+  Ed25519Program,
+  PublicKey,
+  TransactionInstruction,
+} from "@solana/web3.js";
+import { createHash } from "crypto";
+import { IapVault } from "../target/types/iap_vault";
+
+// Must match PROFILE_ATTESTATION_DOMAIN in the program
+const PROFILE_ATTESTATION_DOMAIN = Buffer.from(
+  "iap_vault:profile_attestation:v1",
+);
+
+// Default metadata of a registered profiler oracle. Helius snapshots of one wallet
+// are accepted at most about once an hour (9000 slots).
+export const DEFAULT_ORACLE_METADATA = {
+  name: "iap-profiler",
+  dataSource: "helius",
+  minUpdateIntervalSlots: new anchor.BN(9_000),
+};
+
+export function getOracleRegistryPda(programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("oracle_registry")],
+    programId,
+  )[0];
+}
+
+// Creates the OracleRegistry PDA if it does not exist yet. `authority` must be the
+// program's upgrade authority.
+export async function initOracleRegistry(
+  provider: anchor.AnchorProvider,
+  program: Program<IapVault>,
+  authority: anchor.web3.Keypair,
+): Promise<void> {
+  const oracleRegistry = getOracleRegistryPda(program.programId);
+  if (await provider.connection.getAccountInfo(oracleRegistry)) {
+    console.log("Oracle registry already initialized");
+    return;
+  }
+
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID,
+  );
+
+  const sig = await program.methods
+    .initOracleRegistry()
+    .accountsPartial({
+      authority: authority.publicKey,
+      oracleRegistry,
+      programData,
+    })
+    .signers([authority])
+    .rpc({ commitment: "confirmed" });
+  console.log("Init oracle registry transaction", sig);
+}
+
+// Registers `oracle` unless it already is. `admin` must be the vault admin.
+export async function addOracle(
+  program: Program<IapVault>,
+  admin: anchor.web3.Keypair,
+  oracle: PublicKey,
+  metadata: typeof DEFAULT_ORACLE_METADATA = DEFAULT_ORACLE_METADATA,
+): Promise<void> {
+  const oracleRegistry = getOracleRegistryPda(program.programId);
+  const registry = await program.account.oracleRegistry.fetch(oracleRegistry);
+  if (registry.oracles.some((entry) => entry.oracle.equals(oracle))) {
+    console.log("Oracle", oracle.toBase58(), "already registered");
+    return;
+  }
+
+  const sig = await program.methods
+    .addOracle(oracle, metadata)
+    .accountsPartial({
+      admin: admin.publicKey,
+      oracleRegistry,
+    })
+    .signers([admin])
+    .rpc({ commitment: "confirmed" });
+  console.log("Add oracle transaction", sig);
+}
+
+// The message a profiler oracle signs for one store_interest_profile submission:
+// domain || owner || sha256(x25519 pubkey || ciphertexts) || nonce (u128 LE) || snapshot slot (u64 LE)
+export function profileAttestationMessage(
+  owner: PublicKey,
+  pubkey: Uint8Array,
+  ciphertexts: number[][],
+  nonce: Uint8Array,
+  snapshotSlot: anchor.BN,
+): Buffer {
+  const ciphertextHash = createHash("sha256");
+  ciphertextHash.update(pubkey);
+  ciphertexts.forEach((c) => ciphertextHash.update(Uint8Array.from(c)));
+
+  return Buffer.concat([
+    PROFILE_ATTESTATION_DOMAIN,
+    owner.toBuffer(),
+    ciphertextHash.digest(),
+    Buffer.from(nonce),
+    snapshotSlot.toArrayLike(Buffer, "le", 8),
+  ]);
+}
+
+// Ed25519Program instruction that must directly precede store_interest_profile
+export function createProfileAttestationIx(
+  oracle: anchor.web3.Keypair,
+  message: Buffer,
+): TransactionInstruction {
+  return Ed25519Program.createInstructionWithPrivateKey({
+    privateKey: oracle.secretKey,
+    message,
+  });
+}
@@ -0,0 +1,82 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { PublicKey } from "@solana/web3.js";
+import { IapVault } from "../target/types/iap_vault";
+
+// Aggregates counting fewer users than kAnonymityFloor are never revealed. Every
+// revealed aggregate carries epsilon = 1.0 differential privacy noise; each campaign
+// can spend 5.0 on audience estimates in total, the tier histogram 10.0.
+export const DEFAULT_PRIVACY_PARAMS = {
+  kAnonymityFloor: 10,
+  epsilonMillis: 1_000,
+  campaignBudgetMillis: 5_000,
+  statsBudgetMillis: 10_000,
+};
+
+export function getPrivacyConfigPda(programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("privacy_config")],
+    programId,
+  )[0];
+}
+
+// Creates the PrivacyConfig PDA if it does not exist yet. `authority` must be the
+// program's upgrade authority.
+export async function initPrivacyConfig(
+  provider: anchor.AnchorProvider,
+  program: Program<IapVault>,
+  authority: anchor.web3.Keypair,
+): Promise<void> {
+  const privacyConfig = getPrivacyConfigPda(program.programId);
+  if (await provider.connection.getAccountInfo(privacyConfig)) {
+    console.log("Privacy config already initialized");
+    return;
+  }
+
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID,
+  );
+
+  const sig = await program.methods
+    .initPrivacyConfig(DEFAULT_PRIVACY_PARAMS)
+    .accountsPartial({
+      authority: authority.publicKey,
+      privacyConfig,
+      programData,
+    })
+    .signers([authority])
+    .rpc({ commitment: "confirmed" });
+  console.log("Init privacy config transaction", sig);
+}
+
+export function getVaultStatsPda(programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("vault_stats")],
+    programId,
+  )[0];
+}
+
+// Creates the VaultStats PDA (encrypted tier histogram) if it does not exist yet.
+// `admin` must be the vault admin.
+export async function initVaultStats(
+  provider: anchor.AnchorProvider,
+  program: Program<IapVault>,
+  admin: anchor.web3.Keypair,
+): Promise<void> {
+  const vaultStats = getVaultStatsPda(program.programId);
+  if (await provider.connection.getAccountInfo(vaultStats)) {
+    console.log("Vault stats already initialized");
+    return;
+  }
+
+  const sig = await program.methods
+    .initVaultStats()
+    .accountsPartial({
+      admin: admin.publicKey,
+      vaultStats,
+    })
+    .signers([admin])
+    .rpc({ commitment: "confirmed" });
+  console.log("Init vault stats transaction", sig);
+}
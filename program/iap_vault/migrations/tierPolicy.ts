@@ -0,0 +1,69 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { PublicKey } from "@solana/web3.js";
+import { IapVault } from "../target/types/iap_vault";
+
+const LAMPORTS_PER_SOL = 1_000_000_000;
+
+// Initial thresholds, matching the tiers the profiler script used to compute client-side
+// PLATINUM: >100 NFTs AND >100 SOL (mega whales)
+// GOLD: >49 NFTs AND >30 SOL (whales)
+// SILVER: >20 NFTs AND >10 SOL (active users)
+// BRONZE: Everyone else (basic users)
+export const DEFAULT_TIER_POLICY = {
+  silver: thresholds(20, 10),
+  gold: thresholds(49, 30),
+  platinum: thresholds(100, 100),
+};
+
+function thresholds(nftCountAbove: number, solAbove: number) {
+  return {
+    nftCountAbove,
+    solLamportsAbove: new anchor.BN(solAbove).mul(
+      new anchor.BN(LAMPORTS_PER_SOL),
+    ),
+    minTradingVolumeCents: new anchor.BN(0),
+    minDefiInteractions: 0,
+  };
+}
+
+export function getTierPolicyPda(programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("tier_policy")],
+    programId,
+  )[0];
+}
+
+// Creates the TierPolicy PDA if it does not exist yet. `authority` must be the
+// program's upgrade authority.
+export async function initTierPolicy(
+  provider: anchor.AnchorProvider,
+  program: Program<IapVault>,
+  authority: anchor.web3.Keypair,
+): Promise<void> {
+  const tierPolicy = getTierPolicyPda(program.programId);
+  if (await provider.connection.getAccountInfo(tierPolicy)) {
+    console.log("Tier policy already initialized");
+    return;
+  }
+
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID,
+  );
+
+  const sig = await program.methods
+    .initTierPolicy(
+      DEFAULT_TIER_POLICY.silver,
+      DEFAULT_TIER_POLICY.gold,
+      DEFAULT_TIER_POLICY.platinum,
+    )
+    .accountsPartial({
+      authority: authority.publicKey,
+      tierPolicy,
+      programData,
+    })
+    .signers([authority])
+    .rpc({ commitment: "confirmed" });
+  console.log("Init tier policy transaction", sig);
+}
@@ -0,0 +1,70 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { PublicKey } from "@solana/web3.js";
+import { IapVault } from "../target/types/iap_vault";
+import { getTierPolicyPda } from "./tierPolicy";
+
+// No fees at launch; the recipient must be a rent-exempt account (the admin wallet)
+export function defaultFeeSettings(feeRecipient: PublicKey) {
+  return {
+    feeRecipient,
+    storeFeeLamports: new anchor.BN(0),
+    queryFeeLamports: new anchor.BN(0),
+  };
+}
+
+export function getVaultConfigPda(programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("vault_config")],
+    programId,
+  )[0];
+}
+
+// Creates the VaultConfig PDA if it does not exist yet. `admin` must be the program's
+// upgrade authority, and the tier policy must already be initialized.
+export async function initializeVault(
+  provider: anchor.AnchorProvider,
+  program: Program<IapVault>,
+  admin: anchor.web3.Keypair,
+): Promise<void> {
+  const vaultConfig = getVaultConfigPda(program.programId);
+  if (await provider.connection.getAccountInfo(vaultConfig)) {
+    console.log("Vault config already initialized");
+    return;
+  }
+
+  const [programData] = PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID,
+  );
+
+  const sig = await program.methods
+    .initializeVault(defaultFeeSettings(admin.publicKey))
+    .accountsPartial({
+      admin: admin.publicKey,
+      vaultConfig,
+      tierPolicy: getTierPolicyPda(program.programId),
+      programData,
+    })
+    .signers([admin])
+    .rpc({ commitment: "confirmed" });
+  console.log("Initialize vault transaction", sig);
+}
+
+// Two-step admin transfer. `newAdmin` may be a multisig vault PDA, which then
+// signs accept_admin through the multisig program.
+export async function proposeAdmin(
+  program: Program<IapVault>,
+  admin: anchor.web3.Keypair,
+  newAdmin: PublicKey,
+): Promise<void> {
+  const sig = await program.methods
+    .proposeAdmin(newAdmin)
+    .accountsPartial({
+      admin: admin.publicKey,
+      vaultConfig: getVaultConfigPda(program.programId),
+    })
+    .signers([admin])
+    .rpc({ commitment: "confirmed" });
+  console.log("Propose admin transaction", sig);
+}
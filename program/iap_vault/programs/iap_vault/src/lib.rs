@@ -1,155 +1,5748 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::system_program;
+use anchor_spl::associated_token::{get_associated_token_address_with_program_id, AssociatedToken};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{transfer_fee::TransferFeeConfig, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+};
+use anchor_spl::token_2022_extensions::transfer_fee::{
+    harvest_withheld_tokens_to_mint, transfer_checked_with_fee, HarvestWithheldTokensToMint,
+    TransferCheckedWithFee,
+};
+use anchor_spl::token_interface::{
+    self, CloseAccount, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
 use arcium_anchor::prelude::*;
 use arcium_anchor::{SharedEncryptedStruct, MXEEncryptedStruct};
-use arcis::{Enc, Shared, Mxe, ArcisType, ArcisX25519Pubkey, EncData, ArcisCiphertext, EvalValue, Cipher};
+
+const COMP_DEF_OFFSET_STORE_INTEREST_PROFILE: u32 = comp_def_offset("store_interest_profile");
+const COMP_DEF_OFFSET_COMPUTE_TIER: u32 = comp_def_offset("compute_tier");
+const COMP_DEF_OFFSET_CHECK_ELIGIBILITY: u32 = comp_def_offset("check_eligibility");
+const COMP_DEF_OFFSET_DISCLOSE_FIELDS: u32 = comp_def_offset("disclose_fields");
+const COMP_DEF_OFFSET_READ_MY_PROFILE: u32 = comp_def_offset("read_my_profile");
+const COMP_DEF_OFFSET_STORE_CAMPAIGN_CRITERIA: u32 = comp_def_offset("store_campaign_criteria");
+const COMP_DEF_OFFSET_MATCH_CAMPAIGN_CRITERIA: u32 = comp_def_offset("match_campaign_criteria");
+const COMP_DEF_OFFSET_CLAIM_CAMPAIGN_REWARD: u32 = comp_def_offset("claim_campaign_reward");
+const COMP_DEF_OFFSET_ESTIMATE_AUDIENCE: u32 = comp_def_offset("estimate_audience");
+const COMP_DEF_OFFSET_REVEAL_AUDIENCE_ESTIMATE: u32 = comp_def_offset("reveal_audience_estimate");
+const COMP_DEF_OFFSET_AGGREGATE_TIER_HISTOGRAM: u32 = comp_def_offset("aggregate_tier_histogram");
+const COMP_DEF_OFFSET_REVEAL_TIER_HISTOGRAM: u32 = comp_def_offset("reveal_tier_histogram");
+const COMP_DEF_OFFSET_MEDIAN_INTEREST_PROFILE: u32 = comp_def_offset("median_interest_profile");
 
 declare_id!("9tNsfwyCDBFZRmjuYty4AHpWXziRa26nGjtJAd6qmiR1");
 
-// Interest Profile structure matching TypeScript InterestProfile
-// Fields: tier (u8), nftCount (u32), solBalanceLamports (u64), tradingVolumeCents (u64), tokenHoldings (u32), defiInteractions (u32)
-// Represented as a tuple for Arcium encryption
-type InterestProfileTuple = (u8, u32, u64, u64, u32, u32);
+// Interest Profile structure matching TypeScript InterestProfile
+// Fields: tier (u8), nftCount (u32), solBalanceLamports (u64), tradingVolumeCents (u64), tokenHoldings (u32), defiInteractions (u32)
+// Represented as a tuple for Arcium encryption
+pub type InterestProfileTuple = (u8, u32, u64, u64, u32, u32);
+
+// Number of encrypted fields in InterestProfileTuple (one ciphertext per field)
+pub const PROFILE_FIELD_COUNT: usize = 6;
+// Field masks over InterestProfileTuple: bit i selects field i (bit 0 = tier,
+// bit 1 = nftCount, ...), as used by disclose_fields
+pub const PROFILE_FIELD_MASK_ALL: u8 = (1 << PROFILE_FIELD_COUNT) - 1;
+
+// PDA seeds. A user's profile is always [USER_PROFILE_SEED, owner], which lets
+// campaigns and claims address the stored profile of any wallet directly.
+pub const USER_PROFILE_SEED: &[u8] = b"user_profile";
+pub const TIER_POLICY_SEED: &[u8] = b"tier_policy";
+pub const CAMPAIGN_SEED: &[u8] = b"campaign";
+pub const CAMPAIGN_CRITERIA_SEED: &[u8] = b"campaign_criteria";
+pub const CLAIM_RECORD_SEED: &[u8] = b"claim";
+pub const AUDIENCE_ESTIMATE_SEED: &[u8] = b"audience_estimate";
+pub const PRIVACY_CONFIG_SEED: &[u8] = b"privacy_config";
+pub const VAULT_STATS_SEED: &[u8] = b"vault_stats";
+pub const ORACLE_REGISTRY_SEED: &[u8] = b"oracle_registry";
+pub const PENDING_SUBMISSION_SEED: &[u8] = b"pending_submission";
+pub const VAULT_CONFIG_SEED: &[u8] = b"vault_config";
+pub const ADVERTISER_STATE_SEED: &[u8] = b"advertiser_state";
+pub const ELIGIBILITY_CONSENT_SEED: &[u8] = b"eligibility_consent";
+
+// Current layout version of UserProfile, stored in the account since version 2.
+// 0: unversioned layouts from before the version byte existed. migrate_profile grows
+//    them with zeroed bytes, so their version reads as 0.
+// 1: never stored, only reported in profile events before the version byte existed
+// 2: version, created_at and updated_at
+// 3: oracle attestation (attested_by, snapshot_slot, oracle_id, their pending values
+//    and the stale flag)
+pub const PROFILE_SCHEMA_VERSION: u8 = 3;
+
+// Location of the Enc<Mxe, InterestProfile> ciphertexts inside a UserProfile account,
+// used to pass the stored profile to circuits by account reference.
+// 8 (discriminator) + 16 (nonce)
+const PROFILE_CIPHERTEXTS_OFFSET: u32 = 8 + 16;
+const PROFILE_CIPHERTEXTS_LEN: u32 = 32 * PROFILE_FIELD_COUNT as u32;
+
+// CampaignCriteria mirrors the InterestProfileTuple layout field for field
+// (min tier, NFTs, lamports, volume cents, token holdings, DeFi interactions)
+pub const CRITERIA_FIELD_COUNT: usize = PROFILE_FIELD_COUNT;
+// Enc<Mxe, CampaignCriteria> ciphertexts inside a CampaignCriteria account
+// 8 (discriminator) + 16 (nonce)
+const CRITERIA_CIPHERTEXTS_OFFSET: u32 = 8 + 16;
+const CRITERIA_CIPHERTEXTS_LEN: u32 = 32 * CRITERIA_FIELD_COUNT as u32;
+// Criteria an advertiser can ever store. Every criteria set is another threshold to
+// match profiles against, so unlimited criteria would let an advertiser bisect a
+// profile's fields one match at a time.
+pub const MAX_CRITERIA_PER_ADVERTISER: u32 = 16;
+
+// Slots after which anyone can settle a claim still waiting for its callback as
+// Failed (expire_pending_claim), about an hour
+pub const CLAIM_TIMEOUT_SLOTS: u64 = 9_000;
+
+// Profiles per estimate_audience / aggregate_tier_histogram computation, fixed by
+// the circuit signatures
+pub const PROFILE_BATCH_SIZE: usize = 4;
+// Enc<Mxe, u32> running count inside an AudienceEstimate account
+// 8 (discriminator) + 16 (nonce)
+const AUDIENCE_COUNT_OFFSET: u32 = 8 + 16;
+const AUDIENCE_COUNT_LEN: u32 = 32;
+
+// Enc<Mxe, TierHistogram> (one count per tier, BRONZE to PLATINUM) inside VaultStats
+pub const TIER_COUNT: usize = 4;
+// 8 (discriminator) + 16 (nonce)
+const HISTOGRAM_CIPHERTEXTS_OFFSET: u32 = 8 + 16;
+const HISTOGRAM_CIPHERTEXTS_LEN: u32 = 32 * TIER_COUNT as u32;
+
+// Smallest k-anonymity floor the privacy config accepts: a floor of 1 would let a
+// count reveal whether a single profile matches
+pub const MIN_K_ANONYMITY_FLOOR: u32 = 2;
+
+// Differential privacy. Epsilon is configured in thousandths. The noise circuits cap
+// each geometric sample at MAX_NOISE_TRIALS draws (must match encrypted-ixs); with
+// epsilon >= 0.25 the cap is hit with probability below 1e-7.
+pub const MAX_NOISE_TRIALS: u32 = 64;
+pub const MIN_EPSILON_MILLIS: u32 = 250;
+pub const MAX_EPSILON_MILLIS: u32 = 10_000;
+// exp(-0.001) in Q32 fixed point, see noise_threshold
+const EXP_NEG_MILLI_Q32: u128 = 4_290_674_475;
+
+// Profiler oracles. Every store_interest_profile carries an ed25519 signature of a
+// registered oracle over
+//   PROFILE_ATTESTATION_DOMAIN || owner || sha256(x25519 pubkey || ciphertexts)
+//   || nonce (u128 LE) || snapshot_slot (u64 LE)
+// where snapshot_slot is the slot of the wallet data the profile was built from.
+pub const MAX_ORACLES: usize = 16;
+pub const MAX_ORACLE_NAME_LEN: usize = 32;
+pub const MAX_ORACLE_DATA_SOURCE_LEN: usize = 32;
+pub const PROFILE_ATTESTATION_DOMAIN: &[u8] = b"iap_vault:profile_attestation:v1";
+// Oldest snapshot an attestation may be for, about a day of slots
+pub const MAX_SNAPSHOT_AGE_SLOTS: u64 = 216_000;
+// Layout of an Ed25519Program instruction: 2 header bytes (signature count,
+// padding) followed by 7 u16 offsets per signature
+const ED25519_HEADER_LEN: usize = 2;
+const ED25519_OFFSETS_LEN: usize = 14;
+
+// Most oracle submissions a quorum round can hold, fixed by the median_interest_profile
+// circuit. The registry's quorum_size may be lower.
+pub const MAX_QUORUM_SUBMISSIONS: usize = 5;
+// Quorum of a new registry: a round takes 3 submissions and needs all of them
+pub const DEFAULT_QUORUM_THRESHOLD: u8 = 3;
+pub const DEFAULT_QUORUM_SIZE: u8 = 3;
+// Enc<Shared, InterestProfile> ciphertexts of each submission inside a
+// PendingProfileSubmission account, one PROFILE_CIPHERTEXTS_LEN block per submission
+// 8 (discriminator)
+const SUBMISSION_CIPHERTEXTS_OFFSET: u32 = 8;
+
+#[arcium_program]
+pub mod iap_vault {
+    use super::*;
+
+    // Computation definitions must be initialized (and finalized) once per deployment
+    // before the matching computation can be queued. See migrations/deploy.ts.
+    pub fn init_store_interest_profile_comp_def(
+        ctx: Context<InitStoreInterestProfileCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_compute_tier_comp_def(ctx: Context<InitComputeTierCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_check_eligibility_comp_def(
+        ctx: Context<InitCheckEligibilityCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_disclose_fields_comp_def(ctx: Context<InitDiscloseFieldsCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_read_my_profile_comp_def(ctx: Context<InitReadMyProfileCompDef>) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_store_campaign_criteria_comp_def(
+        ctx: Context<InitStoreCampaignCriteriaCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_match_campaign_criteria_comp_def(
+        ctx: Context<InitMatchCampaignCriteriaCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_claim_campaign_reward_comp_def(
+        ctx: Context<InitClaimCampaignRewardCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_estimate_audience_comp_def(
+        ctx: Context<InitEstimateAudienceCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_reveal_audience_estimate_comp_def(
+        ctx: Context<InitRevealAudienceEstimateCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_aggregate_tier_histogram_comp_def(
+        ctx: Context<InitAggregateTierHistogramCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_reveal_tier_histogram_comp_def(
+        ctx: Context<InitRevealTierHistogramCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    pub fn init_median_interest_profile_comp_def(
+        ctx: Context<InitMedianInterestProfileCompDef>,
+    ) -> Result<()> {
+        init_comp_def(ctx.accounts, None, None)?;
+        Ok(())
+    }
+
+    // Store the full Interest Profile (6 fields) encrypted in Arcium MXE
+    //
+    // The program cannot decrypt anything itself: it queues the `store_interest_profile`
+    // circuit, which re-encrypts the user's Shared ciphertexts for the MXE. The result is
+    // written to the user's profile in `store_interest_profile_callback`.
+    //
+    // The instruction right before this one must be an Ed25519Program signature of a
+    // registered profiler oracle over the submission (see PROFILE_ATTESTATION_DOMAIN),
+    // so only profiles built by a trusted oracle from `snapshot_slot` data are accepted.
+    pub fn store_interest_profile(
+        ctx: Context<StoreInterestProfile>,
+        computation_offset: u64,
+        ciphertexts: Vec<[u8; 32]>, // 6 fields encrypted by the user (Helius script)
+        pubkey: [u8; 32],
+        nonce: u128,
+        snapshot_slot: u64,
+    ) -> Result<()> {
+        let ciphertexts = validate_shared_input(ciphertexts, &pubkey)?;
+        collect_fee(
+            &ctx.accounts.user,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            ctx.accounts.vault_config.fees.store_fee_lamports,
+        )?;
+        let message = profile_attestation_message(
+            &ctx.accounts.user.key(),
+            &pubkey,
+            &ciphertexts,
+            nonce,
+            snapshot_slot,
+        );
+        let oracle = verify_oracle_attestation(
+            &ctx.accounts.instructions_sysvar,
+            &ctx.accounts.oracle_registry,
+            &message,
+        )?
+        .clone();
+        let current_slot = Clock::get()?.slot;
+        require!(
+            snapshot_slot <= current_slot
+                && current_slot - snapshot_slot <= MAX_SNAPSHOT_AGE_SLOTS,
+            IapError::StaleAttestation
+        );
+
+        let computation_account = ctx.accounts.computation_account.key();
+        let user_profile = &mut ctx.accounts.user_profile;
+        if user_profile.owner == Pubkey::default() {
+            // Freshly created by init_if_needed
+            user_profile.owner = ctx.accounts.user.key();
+            user_profile.version = PROFILE_SCHEMA_VERSION;
+            user_profile.created_at = Clock::get()?.slot;
+        }
+        require_keys_eq!(
+            user_profile.owner,
+            ctx.accounts.user.key(),
+            IapError::UnauthorizedUpdater
+        );
+        // Reusing a nonce with the same shared secret leaks plaintext relations
+        require!(user_profile.last_input_nonce != nonce, IapError::NonceReuse);
+        // An older attestation can't be replayed to roll the profile back
+        require!(
+            snapshot_slot > user_profile.snapshot_slot,
+            IapError::StaleAttestation
+        );
+        // Snapshots of one wallet must be at least the oracle's update interval apart
+        require!(
+            user_profile.snapshot_slot == 0
+                || snapshot_slot - user_profile.snapshot_slot >= oracle.min_update_interval_slots,
+            IapError::UpdateTooFrequent
+        );
+        user_profile.last_input_nonce = nonce;
+        user_profile.begin_pending(computation_account, computation_offset);
+        user_profile.pending_attested_by = oracle.oracle;
+        user_profile.pending_snapshot_slot = snapshot_slot;
+        user_profile.pending_oracle_id = oracle.oracle_id;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        // Enc<Shared, InterestProfile> = x25519 pubkey + nonce + one ciphertext per field,
+        // in the same order as InterestProfileTuple.
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pubkey)
+            .plaintext_u128(nonce);
+        let args = push_profile_ciphertexts(args, &ciphertexts);
+        let args = push_tier_policy(args, &ctx.accounts.tier_policy).build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![StoreInterestProfileCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.user_profile.key(),
+                    is_writable: true,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "store_interest_profile")]
+    pub fn store_interest_profile_callback(
+        ctx: Context<StoreInterestProfileCallback>,
+        output: SignedComputationOutputs<StoreInterestProfileOutput>,
+    ) -> Result<()> {
+        let computation_offset = ctx
+            .accounts
+            .user_profile
+            .complete_pending(ctx.accounts.computation_account.key())?;
+        let slot = Clock::get()?.slot;
+
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(StoreInterestProfileOutput {
+                field_0:
+                    StoreInterestProfileOutputStruct0 {
+                        field_0: profile,
+                        field_1: policy_version,
+                    },
+            }) => (profile, policy_version),
+            Err(_) => {
+                // Recorded instead of reverting so indexers see the failure
+                emit!(ComputationFailed {
+                    owner: ctx.accounts.user_profile.owner,
+                    slot,
+                    computation_offset,
+                    circuit: "store_interest_profile".to_string(),
+                });
+                return Ok(());
+            }
+        };
+
+        ctx.accounts
+            .user_profile
+            .store_attested_profile(o.0, o.1, computation_offset, slot);
+        Ok(())
+    }
+
+    // Quorum path for storing a profile. The owner opts in by opening a round, which
+    // creates their (empty) profile if needed. Up to the registry's quorum_size (N)
+    // registered oracles then submit their own Enc<Shared, InterestProfile> for the
+    // owner, and once quorum_threshold (M) did, finalize_oracle_quorum stores the
+    // per-field median, so no single oracle decides any metric. A successful round
+    // closes; the owner opens the next one.
+    pub fn open_quorum_round(ctx: Context<OpenQuorumRound>) -> Result<()> {
+        let owner = ctx.accounts.user.key();
+        let slot = Clock::get()?.slot;
+        let user_profile = &mut ctx.accounts.user_profile;
+        if user_profile.owner == Pubkey::default() {
+            // Freshly created by init_if_needed
+            user_profile.owner = owner;
+            user_profile.version = PROFILE_SCHEMA_VERSION;
+            user_profile.created_at = slot;
+        }
+        require_keys_eq!(user_profile.owner, owner, IapError::UnauthorizedUpdater);
+
+        let submission = &mut ctx.accounts.pending_submission;
+        if submission.owner == Pubkey::default() {
+            // Freshly created by init_if_needed
+            submission.owner = owner;
+            submission.bump = ctx.bumps.pending_submission;
+        }
+        require!(
+            submission.pending_computation == Pubkey::default(),
+            IapError::QuorumInProgress
+        );
+        // Opening an already open round keeps its submissions
+        if !submission.round_open {
+            submission.round_open = true;
+            submission.count = 0;
+        }
+
+        emit!(QuorumRoundOpened { owner, slot });
+        Ok(())
+    }
+
+    // An oracle's submission to the owner's open quorum round, signed by the oracle
+    pub fn submit_oracle_profile(
+        ctx: Context<SubmitOracleProfile>,
+        owner: Pubkey,
+        ciphertexts: Vec<[u8; 32]>,
+        pubkey: [u8; 32],
+        nonce: u128,
+        snapshot_slot: u64,
+    ) -> Result<()> {
+        let ciphertexts = validate_shared_input(ciphertexts, &pubkey)?;
+        let oracle = ctx
+            .accounts
+            .oracle_registry
+            .find_by_key(&ctx.accounts.oracle.key())
+            .ok_or(IapError::UnknownOracle)?;
+        let slot = Clock::get()?.slot;
+        require!(
+            snapshot_slot <= slot && slot - snapshot_slot <= MAX_SNAPSHOT_AGE_SLOTS,
+            IapError::StaleAttestation
+        );
+
+        let submission = &mut ctx.accounts.pending_submission;
+        require!(
+            submission.pending_computation == Pubkey::default(),
+            IapError::QuorumInProgress
+        );
+        // A round that never reached the quorum expires with its oldest snapshot
+        if submission.count > 0 && slot - submission.round_started_at > MAX_SNAPSHOT_AGE_SLOTS {
+            submission.count = 0;
+        }
+        if submission.count == 0 {
+            submission.round_started_at = slot;
+            submission.min_update_interval_slots = 0;
+        }
+
+        let index = usize::from(submission.count);
+        require!(
+            index < usize::from(ctx.accounts.oracle_registry.quorum_size),
+            IapError::QuorumComplete
+        );
+        require!(
+            !submission.oracle_ids[..index].contains(&oracle.oracle_id),
+            IapError::DuplicateOracleSubmission
+        );
+        submission.ciphertexts[index] = ciphertexts;
+        submission.encryption_keys[index] = pubkey;
+        submission.nonces[index] = nonce;
+        submission.oracle_ids[index] = oracle.oracle_id;
+        submission.snapshot_slots[index] = snapshot_slot;
+        submission.min_update_interval_slots = submission
+            .min_update_interval_slots
+            .max(oracle.min_update_interval_slots);
+        submission.count += 1;
+
+        emit!(OracleProfileSubmitted {
+            owner,
+            oracle_id: oracle.oracle_id,
+            oracle: oracle.oracle,
+            snapshot_slot,
+            submissions: submission.count,
+            slot,
+        });
+        Ok(())
+    }
+
+    // Permissionless: queue the median_interest_profile circuit once the round has at
+    // least quorum_threshold submissions. The stored profile is attested as of the
+    // oldest snapshot of the round, by the oracle that submitted it, so it is flagged
+    // stale like any other profile once that oracle is removed.
+    pub fn finalize_oracle_quorum(
+        ctx: Context<FinalizeOracleQuorum>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let computation_account = ctx.accounts.computation_account.key();
+        let oracle_registry = &ctx.accounts.oracle_registry;
+        let submission = &mut ctx.accounts.pending_submission;
+        let user_profile = &mut ctx.accounts.user_profile;
+        let count = usize::from(submission.count);
+        require!(
+            count >= usize::from(oracle_registry.quorum_threshold),
+            IapError::QuorumIncomplete
+        );
+        // A computation superseded by a later store on the profile never calls back
+        // successfully, so the round can be finalized again
+        require!(
+            submission.pending_computation == Pubkey::default()
+                || submission.pending_computation != user_profile.pending_computation,
+            IapError::QuorumInProgress
+        );
+        require!(
+            submission.oracle_ids[..count]
+                .iter()
+                .all(|id| oracle_registry.find_by_id(*id).is_some()),
+            IapError::UnknownOracle
+        );
+
+        // Every snapshot must still be recent enough for a single-oracle store
+        let slot = Clock::get()?.slot;
+        require!(
+            submission.snapshot_slots[..count]
+                .iter()
+                .all(|snapshot| slot.saturating_sub(*snapshot) <= MAX_SNAPSHOT_AGE_SLOTS),
+            IapError::StaleAttestation
+        );
+        let oldest = (0..count)
+            .min_by_key(|i| submission.snapshot_slots[*i])
+            .ok_or(IapError::QuorumIncomplete)?;
+        let snapshot_slot = submission.snapshot_slots[oldest];
+        let oldest_oracle = oracle_registry
+            .find_by_id(submission.oracle_ids[oldest])
+            .ok_or(IapError::UnknownOracle)?;
+        require!(
+            snapshot_slot > user_profile.snapshot_slot,
+            IapError::StaleAttestation
+        );
+        require!(
+            user_profile.snapshot_slot == 0
+                || snapshot_slot - user_profile.snapshot_slot
+                    >= submission.min_update_interval_slots,
+            IapError::UpdateTooFrequent
+        );
+
+        submission.pending_computation = computation_account;
+        user_profile.begin_pending(computation_account, computation_offset);
+        user_profile.pending_attested_by = oldest_oracle.oracle;
+        user_profile.pending_snapshot_slot = snapshot_slot;
+        user_profile.pending_oracle_id = oldest_oracle.oracle_id;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        // One Enc<Shared, InterestProfile> per circuit input, each under its oracle's
+        // key. Inputs past `count` repeat the first submission; the circuit ignores them.
+        let submission = &ctx.accounts.pending_submission;
+        let mut args = ArgBuilder::new();
+        for input in 0..MAX_QUORUM_SUBMISSIONS {
+            let i = if input < count { input } else { 0 };
+            args = args
+                .x25519_pubkey(submission.encryption_keys[i])
+                .plaintext_u128(submission.nonces[i])
+                .account(
+                    submission.key(),
+                    SUBMISSION_CIPHERTEXTS_OFFSET + i as u32 * PROFILE_CIPHERTEXTS_LEN,
+                    PROFILE_CIPHERTEXTS_LEN,
+                );
+        }
+        let args = args.plaintext_u8(submission.count);
+        let args = push_tier_policy(args, &ctx.accounts.tier_policy).build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![MedianInterestProfileCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.user_profile.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.pending_submission.key(),
+                        is_writable: true,
+                    },
+                ],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "median_interest_profile")]
+    pub fn median_interest_profile_callback(
+        ctx: Context<MedianInterestProfileCallback>,
+        output: SignedComputationOutputs<MedianInterestProfileOutput>,
+    ) -> Result<()> {
+        let computation_offset = ctx
+            .accounts
+            .user_profile
+            .complete_pending(ctx.accounts.computation_account.key())?;
+        let slot = Clock::get()?.slot;
+        let submission = &mut ctx.accounts.pending_submission;
+        submission.pending_computation = Pubkey::default();
+
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(MedianInterestProfileOutput {
+                field_0:
+                    MedianInterestProfileOutputStruct0 {
+                        field_0: profile,
+                        field_1: policy_version,
+                    },
+            }) => (profile, policy_version),
+            Err(_) => {
+                // The submissions are kept, so the round can be finalized again
+                emit!(ComputationFailed {
+                    owner: ctx.accounts.user_profile.owner,
+                    slot,
+                    computation_offset,
+                    circuit: "median_interest_profile".to_string(),
+                });
+                return Ok(());
+            }
+        };
+
+        // The round is over; the owner opens the next one
+        submission.count = 0;
+        submission.round_open = false;
+        ctx.accounts
+            .user_profile
+            .store_attested_profile(o.0, o.1, computation_offset, slot);
+        Ok(())
+    }
+
+    // Re-derive the tier of the caller's stored profile inside MPC under the
+    // current TierPolicy. The tier in a stored profile is always MPC-derived, so
+    // this is only needed after the policy has been updated.
+    pub fn compute_tier(ctx: Context<ComputeTier>, computation_offset: u64) -> Result<()> {
+        // Re-tiering an empty profile would write back encrypted zeros that look stored
+        ctx.accounts.user_profile.require_current()?;
+        let computation_account = ctx.accounts.computation_account.key();
+        ctx.accounts
+            .user_profile
+            .begin_pending(computation_account, computation_offset);
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        // Enc<Mxe, InterestProfile> is read straight from the UserProfile account
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.user_profile.interest_profile.inner.nonce)
+            .account(
+                ctx.accounts.user_profile.key(),
+                PROFILE_CIPHERTEXTS_OFFSET,
+                PROFILE_CIPHERTEXTS_LEN,
+            );
+        let args = push_tier_policy(args, &ctx.accounts.tier_policy).build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![ComputeTierCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.user_profile.key(),
+                    is_writable: true,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "compute_tier")]
+    pub fn compute_tier_callback(
+        ctx: Context<ComputeTierCallback>,
+        output: SignedComputationOutputs<ComputeTierOutput>,
+    ) -> Result<()> {
+        let computation_offset = ctx
+            .accounts
+            .user_profile
+            .complete_pending(ctx.accounts.computation_account.key())?;
+        let slot = Clock::get()?.slot;
+
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(ComputeTierOutput {
+                field_0:
+                    ComputeTierOutputStruct0 {
+                        field_0: profile,
+                        field_1: policy_version,
+                    },
+            }) => (profile, policy_version),
+            Err(_) => {
+                emit!(ComputationFailed {
+                    owner: ctx.accounts.user_profile.owner,
+                    slot,
+                    computation_offset,
+                    circuit: "compute_tier".to_string(),
+                });
+                return Ok(());
+            }
+        };
+
+        let user_profile = &mut ctx.accounts.user_profile;
+        user_profile.set_encrypted_profile(o.0);
+        user_profile.tier_policy_version = o.1;
+        user_profile.updated_at = slot;
+
+        emit!(TierComputed {
+            owner: user_profile.owner,
+            slot,
+            schema_version: user_profile.version,
+            computation_offset,
+            tier_policy_version: o.1,
+        });
+
+        Ok(())
+    }
+
+    // Check a stored profile against plaintext criteria supplied by the requester. The
+    // MPC compares them against the Enc<Mxe, InterestProfile> and only the yes/no
+    // answer is revealed, in the EligibilityChecked event emitted by the callback.
+    // Each check uses up one query of the owner's consent for this requester.
+    pub fn check_eligibility(
+        ctx: Context<CheckEligibility>,
+        computation_offset: u64,
+        criteria: EligibilityCriteria,
+    ) -> Result<()> {
+        ctx.accounts.user_profile.require_current()?;
+
+        ctx.accounts
+            .eligibility_consent
+            .use_query(&ctx.accounts.user_profile, Clock::get()?.slot)?;
+
+        collect_fee(
+            &ctx.accounts.requester,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            ctx.accounts.vault_config.fees.query_fee_lamports,
+        )?;
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.user_profile.interest_profile.inner.nonce)
+            .account(
+                ctx.accounts.user_profile.key(),
+                PROFILE_CIPHERTEXTS_OFFSET,
+                PROFILE_CIPHERTEXTS_LEN,
+            );
+        let args = push_eligibility_criteria(args, &criteria).build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![CheckEligibilityCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.user_profile.key(),
+                        is_writable: false,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.requester.key(),
+                        is_writable: false,
+                    },
+                ],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "check_eligibility")]
+    pub fn check_eligibility_callback(
+        ctx: Context<CheckEligibilityCallback>,
+        output: SignedComputationOutputs<CheckEligibilityOutput>,
+    ) -> Result<()> {
+        let eligible = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(CheckEligibilityOutput { field_0 }) => field_0,
+            Err(_) => return Err(IapError::AbortedComputation.into()),
+        };
+
+        emit!(EligibilityChecked {
+            owner: ctx.accounts.user_profile.owner,
+            requester: ctx.accounts.requester.key(),
+            computation_account: ctx.accounts.computation_account.key(),
+            eligible,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Let `requester` check the caller's profile, at most `max_queries` times and until
+    // `expires_at_slot`, with check_eligibility or, for an advertiser, with
+    // match_campaign_criteria. Every check reveals one bit for criteria of the
+    // requester's choice, so the query count bounds how far it can bisect the profile.
+    // Granting again replaces the previous consent for the same requester.
+    pub fn grant_eligibility_consent(
+        ctx: Context<GrantEligibilityConsent>,
+        requester: Pubkey,
+        max_queries: u32,
+        expires_at_slot: u64,
+    ) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        require!(
+            max_queries > 0 && expires_at_slot > slot,
+            IapError::InvalidConsent
+        );
+
+        let consent = &mut ctx.accounts.eligibility_consent;
+        consent.owner = ctx.accounts.user.key();
+        consent.requester = requester;
+        consent.queries_remaining = max_queries;
+        consent.expires_at_slot = expires_at_slot;
+        consent.bump = ctx.bumps.eligibility_consent;
+        consent.profile_created_at = ctx.accounts.user_profile.created_at;
+
+        emit!(EligibilityConsentGranted {
+            owner: consent.owner,
+            requester,
+            max_queries,
+            expires_at_slot,
+            slot,
+        });
+        Ok(())
+    }
+
+    // Withdraw a consent and refund its rent. Checks already queued still complete.
+    // Not gated by the pause flags: an owner can always take back access to their
+    // profile.
+    pub fn revoke_eligibility_consent(ctx: Context<RevokeEligibilityConsent>) -> Result<()> {
+        emit!(EligibilityConsentRevoked {
+            owner: ctx.accounts.eligibility_consent.owner,
+            requester: ctx.accounts.eligibility_consent.requester,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Let the owner see what the vault holds: the MPC re-encrypts the stored
+    // Enc<Mxe, InterestProfile> to the owner's fresh x25519 `pubkey` with `nonce`, and
+    // the callback emits it in ProfileReadBack for the SDK to decrypt.
+    pub fn read_my_profile(
+        ctx: Context<ReadMyProfile>,
+        computation_offset: u64,
+        pubkey: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.user_profile.has_encrypted_profile(),
+            IapError::ProfileNotStored
+        );
+        require!(pubkey != [0u8; 32], IapError::InvalidEncryptionKey);
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pubkey)
+            .plaintext_u128(nonce)
+            .plaintext_u128(ctx.accounts.user_profile.interest_profile.inner.nonce)
+            .account(
+                ctx.accounts.user_profile.key(),
+                PROFILE_CIPHERTEXTS_OFFSET,
+                PROFILE_CIPHERTEXTS_LEN,
+            )
+            .build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![ReadMyProfileCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.user_profile.key(),
+                    is_writable: false,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "read_my_profile")]
+    pub fn read_my_profile_callback(
+        ctx: Context<ReadMyProfileCallback>,
+        output: SignedComputationOutputs<ReadMyProfileOutput>,
+    ) -> Result<()> {
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(ReadMyProfileOutput { field_0 }) => field_0,
+            Err(_) => return Err(IapError::AbortedComputation.into()),
+        };
+
+        let user_profile = &ctx.accounts.user_profile;
+        emit!(ProfileReadBack {
+            owner: user_profile.owner,
+            encryption_key: o.encryption_key,
+            nonce: o.nonce,
+            ciphertexts: o.ciphertexts,
+            tier_policy_version: user_profile.tier_policy_version,
+            updated_at: user_profile.updated_at,
+            computation_account: ctx.accounts.computation_account.key(),
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Share selected fields of the caller's stored profile with a third party: the MPC
+    // re-encrypts them to `recipient_pubkey` (x25519) with `recipient_nonce`, zeroing
+    // the fields not in `field_mask`. The result is emitted in ProfileFieldsDisclosed
+    // for the recipient to decrypt off-chain; nothing is stored.
+    pub fn disclose_fields(
+        ctx: Context<DiscloseFields>,
+        computation_offset: u64,
+        recipient_pubkey: [u8; 32],
+        recipient_nonce: u128,
+        field_mask: u8,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.user_profile.has_encrypted_profile(),
+            IapError::ProfileNotStored
+        );
+        require!(
+            field_mask != 0 && field_mask & !PROFILE_FIELD_MASK_ALL == 0,
+            IapError::InvalidFieldMask
+        );
+        require!(recipient_pubkey != [0u8; 32], IapError::InvalidEncryptionKey);
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .x25519_pubkey(recipient_pubkey)
+            .plaintext_u128(recipient_nonce)
+            .plaintext_u128(ctx.accounts.user_profile.interest_profile.inner.nonce)
+            .account(
+                ctx.accounts.user_profile.key(),
+                PROFILE_CIPHERTEXTS_OFFSET,
+                PROFILE_CIPHERTEXTS_LEN,
+            )
+            .plaintext_u8(field_mask)
+            .build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![DiscloseFieldsCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.user_profile.key(),
+                    is_writable: false,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "disclose_fields")]
+    pub fn disclose_fields_callback(
+        ctx: Context<DiscloseFieldsCallback>,
+        output: SignedComputationOutputs<DiscloseFieldsOutput>,
+    ) -> Result<()> {
+        let (disclosed, field_mask) = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(DiscloseFieldsOutput {
+                field_0:
+                    DiscloseFieldsOutputStruct0 {
+                        field_0: disclosed,
+                        field_1: field_mask,
+                    },
+            }) => (disclosed, field_mask),
+            Err(_) => return Err(IapError::AbortedComputation.into()),
+        };
+
+        emit!(ProfileFieldsDisclosed {
+            owner: ctx.accounts.user_profile.owner,
+            recipient_pubkey: disclosed.encryption_key,
+            field_mask,
+            nonce: disclosed.nonce,
+            ciphertexts: disclosed.ciphertexts,
+            computation_account: ctx.accounts.computation_account.key(),
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Store a campaign's targeting criteria encrypted: the advertiser submits
+    // Enc<Shared, CampaignCriteria> and the MXE re-encrypts it to Enc<Mxe, _>.
+    // Criteria are immutable once stored; a new criteria_id is needed to change them.
+    // If the store computation fails, the same criteria_id can be stored again.
+    pub fn store_campaign_criteria(
+        ctx: Context<StoreCampaignCriteria>,
+        computation_offset: u64,
+        criteria_id: u64,
+        ciphertexts: Vec<[u8; 32]>,
+        pubkey: [u8; 32],
+        nonce: u128,
+    ) -> Result<()> {
+        let ciphertexts = validate_shared_input(ciphertexts, &pubkey)?;
+
+        let campaign_criteria = &mut ctx.accounts.campaign_criteria;
+        require!(
+            !campaign_criteria.ready && campaign_criteria.pending_computation == Pubkey::default(),
+            IapError::CriteriaAlreadyStored
+        );
+        // A retry after a failed store does not count against the limit again
+        if campaign_criteria.advertiser == Pubkey::default() {
+            let advertiser_state = &mut ctx.accounts.advertiser_state;
+            require!(
+                advertiser_state.criteria_count < MAX_CRITERIA_PER_ADVERTISER,
+                IapError::CriteriaLimitReached
+            );
+            advertiser_state.advertiser = ctx.accounts.advertiser.key();
+            advertiser_state.criteria_count += 1;
+            advertiser_state.bump = ctx.bumps.advertiser_state;
+        }
+        campaign_criteria.advertiser = ctx.accounts.advertiser.key();
+        campaign_criteria.criteria_id = criteria_id;
+        campaign_criteria.pending_computation = ctx.accounts.computation_account.key();
+        campaign_criteria.pending_computation_offset = computation_offset;
+        campaign_criteria.bump = ctx.bumps.campaign_criteria;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .x25519_pubkey(pubkey)
+            .plaintext_u128(nonce);
+        let args = push_profile_ciphertexts(args, &ciphertexts).build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![StoreCampaignCriteriaCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.campaign_criteria.key(),
+                    is_writable: true,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "store_campaign_criteria")]
+    pub fn store_campaign_criteria_callback(
+        ctx: Context<StoreCampaignCriteriaCallback>,
+        output: SignedComputationOutputs<StoreCampaignCriteriaOutput>,
+    ) -> Result<()> {
+        let campaign_criteria = &mut ctx.accounts.campaign_criteria;
+        require_keys_eq!(
+            campaign_criteria.pending_computation,
+            ctx.accounts.computation_account.key(),
+            IapError::StaleComputation
+        );
+        campaign_criteria.pending_computation = Pubkey::default();
+
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(StoreCampaignCriteriaOutput { field_0 }) => field_0,
+            Err(_) => {
+                // Left not ready, so the advertiser can store the criteria again
+                emit!(ComputationFailed {
+                    owner: campaign_criteria.advertiser,
+                    slot: Clock::get()?.slot,
+                    computation_offset: campaign_criteria.pending_computation_offset,
+                    circuit: "store_campaign_criteria".to_string(),
+                });
+                return Ok(());
+            }
+        };
+
+        campaign_criteria.encrypted_criteria = MXEWrapper {
+            inner: MXEEncryptedStruct {
+                nonce: o.nonce,
+                ciphertexts: o.ciphertexts,
+            },
+        };
+        campaign_criteria.ready = true;
+
+        emit!(CampaignCriteriaStored {
+            advertiser: campaign_criteria.advertiser,
+            criteria: campaign_criteria.key(),
+            criteria_id: campaign_criteria.criteria_id,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Match a stored profile against the advertiser's encrypted criteria. Only the
+    // advertiser owning the criteria can ask, and only the boolean is revealed.
+    // The criteria must back one of the advertiser's campaigns that is running and
+    // funded for another reward, so matching stays tied to a real offer. Like
+    // check_eligibility, every match uses up one query of the owner's consent for
+    // this advertiser.
+    pub fn match_campaign_criteria(
+        ctx: Context<MatchCampaignCriteria>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        ctx.accounts.user_profile.require_current()?;
+        require!(
+            ctx.accounts.campaign_criteria.ready,
+            IapError::CriteriaNotReady
+        );
+        let slot = Clock::get()?.slot;
+        ctx.accounts.campaign.require_accepting_claims(slot)?;
+        ctx.accounts
+            .eligibility_consent
+            .use_query(&ctx.accounts.user_profile, slot)?;
+        collect_fee(
+            &ctx.accounts.advertiser,
+            &ctx.accounts.fee_recipient,
+            &ctx.accounts.system_program,
+            ctx.accounts.vault_config.fees.query_fee_lamports,
+        )?;
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.user_profile.interest_profile.inner.nonce)
+            .account(
+                ctx.accounts.user_profile.key(),
+                PROFILE_CIPHERTEXTS_OFFSET,
+                PROFILE_CIPHERTEXTS_LEN,
+            )
+            .plaintext_u128(ctx.accounts.campaign_criteria.encrypted_criteria.inner.nonce)
+            .account(
+                ctx.accounts.campaign_criteria.key(),
+                CRITERIA_CIPHERTEXTS_OFFSET,
+                CRITERIA_CIPHERTEXTS_LEN,
+            )
+            .build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![MatchCampaignCriteriaCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.user_profile.key(),
+                        is_writable: false,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.campaign_criteria.key(),
+                        is_writable: false,
+                    },
+                ],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "match_campaign_criteria")]
+    pub fn match_campaign_criteria_callback(
+        ctx: Context<MatchCampaignCriteriaCallback>,
+        output: SignedComputationOutputs<MatchCampaignCriteriaOutput>,
+    ) -> Result<()> {
+        let matched = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(MatchCampaignCriteriaOutput { field_0 }) => field_0,
+            Err(_) => return Err(IapError::AbortedComputation.into()),
+        };
+
+        emit!(CampaignCriteriaMatched {
+            owner: ctx.accounts.user_profile.owner,
+            criteria: ctx.accounts.campaign_criteria.key(),
+            computation_account: ctx.accounts.computation_account.key(),
+            matched,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Create a campaign targeting the advertiser's encrypted criteria, escrowing
+    // `initial_funding` lamports in the campaign PDA to pay rewards from.
+    pub fn create_campaign(
+        ctx: Context<CreateCampaign>,
+        campaign_id: u64,
+        reward_per_match: u64,
+        start_slot: u64,
+        end_slot: u64,
+        max_participants: u32,
+        initial_funding: u64,
+    ) -> Result<()> {
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.open(
+            ctx.accounts.advertiser.key(),
+            campaign_id,
+            &ctx.accounts.campaign_criteria,
+            None,
+            ctx.bumps.campaign,
+        )?;
+        campaign.set_terms(reward_per_match, start_slot, end_slot, max_participants)?;
+
+        if initial_funding > 0 {
+            fund_campaign_escrow(
+                &ctx.accounts.advertiser,
+                campaign,
+                &ctx.accounts.system_program,
+                initial_funding,
+            )?;
+        }
+
+        emit!(CampaignCreated {
+            campaign: campaign.key(),
+            advertiser: campaign.advertiser,
+            criteria: campaign.criteria,
+            reward_mint: None,
+            reward_per_match,
+            start_slot,
+            end_slot,
+            max_participants,
+            escrow_balance: campaign.escrow_balance,
+        });
+        Ok(())
+    }
+
+    // Top up the reward escrow. Anyone may fund a campaign.
+    pub fn fund_campaign(ctx: Context<FundCampaign>, amount: u64) -> Result<()> {
+        require!(amount > 0, IapError::InvalidCampaignParams);
+        let campaign = &mut ctx.accounts.campaign;
+        require!(campaign.reward_mint.is_none(), IapError::RewardMintMismatch);
+        fund_campaign_escrow(
+            &ctx.accounts.funder,
+            campaign,
+            &ctx.accounts.system_program,
+            amount,
+        )?;
+
+        emit!(CampaignFunded {
+            campaign: campaign.key(),
+            funder: ctx.accounts.funder.key(),
+            amount,
+            escrow_balance: campaign.escrow_balance,
+        });
+        Ok(())
+    }
+
+    // Not gated by the pause flags, since a paused campaign accepts no claims
+    pub fn pause_campaign(ctx: Context<UpdateCampaign>) -> Result<()> {
+        let campaign = &mut ctx.accounts.campaign;
+        require!(!campaign.paused, IapError::CampaignPaused);
+        campaign.paused = true;
+
+        emit!(CampaignPauseChanged {
+            campaign: campaign.key(),
+            paused: true,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    pub fn resume_campaign(ctx: Context<ResumeCampaign>) -> Result<()> {
+        let campaign = &mut ctx.accounts.campaign;
+        require!(campaign.paused, IapError::CampaignNotPaused);
+        campaign.paused = false;
+
+        emit!(CampaignPauseChanged {
+            campaign: campaign.key(),
+            paused: false,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Close the campaign and return the unspent escrow (and the rent) to the advertiser.
+    pub fn close_campaign(ctx: Context<CloseCampaign>) -> Result<()> {
+        let campaign = &ctx.accounts.campaign;
+        require!(campaign.reward_mint.is_none(), IapError::RewardMintMismatch);
+        // Escrow reserved for claims still in MPC must stay until they settle, or until
+        // expire_pending_claim gives up on them
+        require!(campaign.pending_claims == 0, IapError::CampaignHasPendingClaims);
+        emit!(CampaignClosed {
+            campaign: campaign.key(),
+            advertiser: campaign.advertiser,
+            refunded_balance: campaign.escrow_balance,
+            participants: campaign.participants,
+        });
+        Ok(())
+    }
+
+    // Same as create_campaign, but rewards are paid in `reward_mint` (SPL Token or
+    // Token-2022) from an associated token account owned by the campaign PDA.
+    pub fn create_token_campaign(
+        ctx: Context<CreateTokenCampaign>,
+        campaign_id: u64,
+        reward_per_match: u64,
+        start_slot: u64,
+        end_slot: u64,
+        max_participants: u32,
+        initial_funding: u64,
+    ) -> Result<()> {
+        validate_reward_mint(&ctx.accounts.reward_mint.to_account_info())?;
+
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.open(
+            ctx.accounts.advertiser.key(),
+            campaign_id,
+            &ctx.accounts.campaign_criteria,
+            Some(ctx.accounts.reward_mint.key()),
+            ctx.bumps.campaign,
+        )?;
+        campaign.set_terms(reward_per_match, start_slot, end_slot, max_participants)?;
+
+        if initial_funding > 0 {
+            fund_campaign_token_escrow(
+                &ctx.accounts.advertiser,
+                &ctx.accounts.advertiser_token_account,
+                &mut ctx.accounts.escrow_vault,
+                &ctx.accounts.reward_mint,
+                &ctx.accounts.token_program,
+                campaign,
+                initial_funding,
+            )?;
+        }
+
+        emit!(CampaignCreated {
+            campaign: campaign.key(),
+            advertiser: campaign.advertiser,
+            criteria: campaign.criteria,
+            reward_mint: campaign.reward_mint,
+            reward_per_match,
+            start_slot,
+            end_slot,
+            max_participants,
+            escrow_balance: campaign.escrow_balance,
+        });
+        Ok(())
+    }
+
+    // Top up the token vault of a token campaign. Anyone may fund a campaign.
+    pub fn fund_token_campaign(ctx: Context<FundTokenCampaign>, amount: u64) -> Result<()> {
+        require!(amount > 0, IapError::InvalidCampaignParams);
+        let received = fund_campaign_token_escrow(
+            &ctx.accounts.funder,
+            &ctx.accounts.funder_token_account,
+            &mut ctx.accounts.escrow_vault,
+            &ctx.accounts.reward_mint,
+            &ctx.accounts.token_program,
+            &mut ctx.accounts.campaign,
+            amount,
+        )?;
+
+        emit!(CampaignFunded {
+            campaign: ctx.accounts.campaign.key(),
+            funder: ctx.accounts.funder.key(),
+            amount: received,
+            escrow_balance: ctx.accounts.campaign.escrow_balance,
+        });
+        Ok(())
+    }
+
+    // Close a token campaign: the whole vault balance goes back to the advertiser,
+    // then the vault and the campaign are closed and their rent refunded.
+    pub fn close_token_campaign(ctx: Context<CloseTokenCampaign>) -> Result<()> {
+        let campaign = &ctx.accounts.campaign;
+        require!(campaign.pending_claims == 0, IapError::CampaignHasPendingClaims);
+
+        let campaign_id = campaign.campaign_id.to_le_bytes();
+        let bump = [campaign.bump];
+        let seeds: &[&[u8]] = &[CAMPAIGN_SEED, campaign.advertiser.as_ref(), &campaign_id, &bump];
+        let signer_seeds = &[seeds];
+        let token_program = ctx.accounts.token_program.to_account_info();
+        let reward_mint = ctx.accounts.reward_mint.to_account_info();
+        let escrow_vault = ctx.accounts.escrow_vault.to_account_info();
+
+        // Includes tokens sent to the vault without going through fund_token_campaign
+        let refunded = ctx.accounts.escrow_vault.amount;
+        if refunded > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    token_program.clone(),
+                    TransferChecked {
+                        from: escrow_vault.clone(),
+                        mint: reward_mint.clone(),
+                        to: ctx.accounts.advertiser_token_account.to_account_info(),
+                        authority: campaign.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                refunded,
+                ctx.accounts.reward_mint.decimals,
+            )?;
+        }
+
+        // Token-2022 refuses to close an account still holding withheld transfer fees
+        if has_transfer_fee(&reward_mint)? {
+            harvest_withheld_tokens_to_mint(
+                CpiContext::new(
+                    token_program.clone(),
+                    HarvestWithheldTokensToMint {
+                        token_program_id: token_program.clone(),
+                        mint: reward_mint.clone(),
+                    },
+                ),
+                vec![escrow_vault.clone()],
+            )?;
+        }
+
+        token_interface::close_account(CpiContext::new_with_signer(
+            token_program,
+            CloseAccount {
+                account: escrow_vault,
+                destination: ctx.accounts.advertiser.to_account_info(),
+                authority: campaign.to_account_info(),
+            },
+            signer_seeds,
+        ))?;
+
+        emit!(CampaignClosed {
+            campaign: campaign.key(),
+            advertiser: campaign.advertiser,
+            refunded_balance: refunded,
+            participants: campaign.participants,
+        });
+        Ok(())
+    }
+
+    // Claim a campaign reward. The caller's stored profile is matched against the
+    // campaign's encrypted criteria inside MPC; the reward is reserved from escrow
+    // now and paid in the callback only if the revealed result is true.
+    // The per-(campaign, user) ClaimRecord makes a second claim impossible; only a
+    // claim whose computation failed can be retried.
+    // Token campaigns also take the reward mint, the campaign vault, the claimant's
+    // token account and the token program; lamport campaigns leave them out.
+    pub fn claim_campaign_reward(
+        ctx: Context<ClaimCampaignReward>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        ctx.accounts.user_profile.require_current()?;
+
+        let (payout_account, token_callback_accounts) = match ctx.accounts.campaign.reward_mint {
+            None => (ctx.accounts.user.key(), Vec::new()),
+            Some(mint) => ctx.accounts.token_payout_accounts(mint)?,
+        };
+
+        let slot = Clock::get()?.slot;
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.require_accepting_claims(slot)?;
+
+        let claim_record = &mut ctx.accounts.claim_record;
+        match claim_record.status {
+            // Freshly created by init_if_needed, or a retry after a failed computation
+            ClaimStatus::Uninitialized | ClaimStatus::Failed => {}
+            _ => return err!(IapError::AlreadyClaimed),
+        }
+
+        campaign.reserved_balance += campaign.reward_per_match;
+        campaign.pending_claims += 1;
+
+        claim_record.campaign = campaign.key();
+        claim_record.claimant = ctx.accounts.user.key();
+        claim_record.payout_account = payout_account;
+        claim_record.status = ClaimStatus::Pending;
+        claim_record.reward = campaign.reward_per_match;
+        claim_record.computation = ctx.accounts.computation_account.key();
+        claim_record.requested_at = slot;
+        claim_record.bump = ctx.bumps.claim_record;
+
+        emit!(RewardClaimRequested {
+            campaign: campaign.key(),
+            claimant: claim_record.claimant,
+            computation_offset,
+            slot,
+        });
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.user_profile.interest_profile.inner.nonce)
+            .account(
+                ctx.accounts.user_profile.key(),
+                PROFILE_CIPHERTEXTS_OFFSET,
+                PROFILE_CIPHERTEXTS_LEN,
+            )
+            .plaintext_u128(ctx.accounts.campaign_criteria.encrypted_criteria.inner.nonce)
+            .account(
+                ctx.accounts.campaign_criteria.key(),
+                CRITERIA_CIPHERTEXTS_OFFSET,
+                CRITERIA_CIPHERTEXTS_LEN,
+            )
+            .build();
+
+        let mut callback_accounts = vec![
+            CallbackAccount {
+                pubkey: ctx.accounts.campaign.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.claim_record.key(),
+                is_writable: true,
+            },
+            CallbackAccount {
+                pubkey: ctx.accounts.user.key(),
+                is_writable: true,
+            },
+        ];
+        // Reach the callback as remaining accounts
+        callback_accounts.extend(token_callback_accounts);
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![ClaimCampaignRewardCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &callback_accounts,
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "claim_campaign_reward")]
+    pub fn claim_campaign_reward_callback(
+        ctx: Context<ClaimCampaignRewardCallback>,
+        output: SignedComputationOutputs<ClaimCampaignRewardOutput>,
+    ) -> Result<()> {
+        require!(
+            ctx.accounts.claim_record.status == ClaimStatus::Pending,
+            IapError::StaleComputation
+        );
+        require_keys_eq!(
+            ctx.accounts.claim_record.computation,
+            ctx.accounts.computation_account.key(),
+            IapError::StaleComputation
+        );
+
+        let verified = output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        );
+
+        let campaign = &mut ctx.accounts.campaign;
+        let claim_record = &mut ctx.accounts.claim_record;
+        let reward = claim_record.reward;
+
+        // The reservation is released whatever the outcome
+        campaign.reserved_balance -= reward;
+        campaign.pending_claims -= 1;
+
+        let eligible = match verified {
+            Ok(ClaimCampaignRewardOutput { field_0 }) => field_0,
+            Err(_) => {
+                // Not the user's fault: leave the claim retryable
+                claim_record.status = ClaimStatus::Failed;
+                emit!(RewardClaimSettled {
+                    campaign: campaign.key(),
+                    claimant: claim_record.claimant,
+                    status: ClaimStatus::Failed,
+                    reward_paid: 0,
+                    transfer_fee: 0,
+                    slot: Clock::get()?.slot,
+                });
+                return Ok(());
+            }
+        };
+
+        // A payout the runtime or the token program would refuse settles the claim as
+        // Failed instead of reverting, so the reservation is released either way. The
+        // claimant can retry once the receiving account is usable again.
+        let (reward_paid, transfer_fee) = if eligible {
+            let transfer_fee = match campaign.reward_mint {
+                None => {
+                    let claimant = &ctx.accounts.claimant;
+                    if Rent::get()?.is_exempt(claimant.lamports() + reward, claimant.data_len()) {
+                        // The campaign PDA is owned by this program, so lamports can be
+                        // moved directly
+                        campaign.sub_lamports(reward)?;
+                        claimant.add_lamports(reward)?;
+                        Some(0)
+                    } else {
+                        None
+                    }
+                }
+                Some(mint) => {
+                    let [reward_mint, escrow_vault, recipient, token_program] =
+                        ctx.remaining_accounts
+                    else {
+                        return err!(IapError::MissingTokenAccounts);
+                    };
+                    require_keys_eq!(reward_mint.key(), mint, IapError::RewardMintMismatch);
+                    require_keys_eq!(
+                        token_program.key(),
+                        *reward_mint.owner,
+                        IapError::RewardMintMismatch
+                    );
+                    require_keys_eq!(
+                        recipient.key(),
+                        claim_record.payout_account,
+                        IapError::Unauthorized
+                    );
+                    if can_transfer_tokens(escrow_vault, recipient, mint, token_program) {
+                        Some(pay_from_token_escrow(
+                            campaign,
+                            reward_mint,
+                            escrow_vault,
+                            recipient,
+                            token_program,
+                            reward,
+                        )?)
+                    } else {
+                        None
+                    }
+                }
+            };
+            match transfer_fee {
+                Some(transfer_fee) => {
+                    campaign.escrow_balance -= reward;
+                    campaign.participants += 1;
+                    claim_record.status = ClaimStatus::Paid;
+                    (reward, transfer_fee)
+                }
+                None => {
+                    claim_record.status = ClaimStatus::Failed;
+                    (0, 0)
+                }
+            }
+        } else {
+            claim_record.status = ClaimStatus::Rejected;
+            (0, 0)
+        };
+
+        emit!(RewardClaimSettled {
+            campaign: campaign.key(),
+            claimant: claim_record.claimant,
+            status: claim_record.status,
+            reward_paid,
+            transfer_fee,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Settle a claim whose callback never arrived, or keeps failing, as Failed and
+    // release its reservation, so the campaign can be closed. Anyone can call this
+    // once CLAIM_TIMEOUT_SLOTS have passed since the claim was queued; a late callback
+    // is then rejected as stale, and the claimant can retry. Not gated by the pause
+    // flags, since it only unlocks funds held for a claim.
+    pub fn expire_pending_claim(ctx: Context<ExpirePendingClaim>) -> Result<()> {
+        let slot = Clock::get()?.slot;
+        let claim_record = &mut ctx.accounts.claim_record;
+        require!(
+            claim_record.status == ClaimStatus::Pending
+                && slot >= claim_record.requested_at + CLAIM_TIMEOUT_SLOTS,
+            IapError::ClaimNotExpired
+        );
+
+        let campaign = &mut ctx.accounts.campaign;
+        campaign.reserved_balance -= claim_record.reward;
+        campaign.pending_claims -= 1;
+        claim_record.status = ClaimStatus::Failed;
+
+        emit!(RewardClaimSettled {
+            campaign: campaign.key(),
+            claimant: claim_record.claimant,
+            status: ClaimStatus::Failed,
+            reward_paid: 0,
+            transfer_fee: 0,
+            slot,
+        });
+        Ok(())
+    }
+
+    // Count, inside MPC, how many of a batch of stored profiles match the advertiser's
+    // encrypted criteria, adding to the running count of the criteria's AudienceEstimate.
+    // Up to PROFILE_BATCH_SIZE UserProfile accounts are passed as remaining accounts,
+    // in strictly ascending key order across all batches so no profile is counted twice.
+    // Nothing is revealed until reveal_audience_estimate.
+    pub fn estimate_audience(ctx: Context<EstimateAudience>, computation_offset: u64) -> Result<()> {
+        require!(
+            ctx.accounts.campaign_criteria.ready,
+            IapError::CriteriaNotReady
+        );
+
+        let audience_estimate = &mut ctx.accounts.audience_estimate;
+        if audience_estimate.criteria == Pubkey::default() {
+            audience_estimate.criteria = ctx.accounts.campaign_criteria.key();
+            audience_estimate.advertiser = ctx.accounts.advertiser.key();
+            audience_estimate.bump = ctx.bumps.audience_estimate;
+        }
+        require!(!audience_estimate.revealed, IapError::EstimateFinalized);
+        require_keys_eq!(
+            audience_estimate.pending_computation,
+            Pubkey::default(),
+            IapError::EstimateInProgress
+        );
+
+        let batch = collect_profile_batch(ctx.remaining_accounts, audience_estimate.last_profile)?;
+        audience_estimate.pending_computation = ctx.accounts.computation_account.key();
+        audience_estimate.pending_computation_offset = computation_offset;
+        audience_estimate.pending_cursor = batch[batch.len() - 1].0;
+        audience_estimate.pending_batch_len = batch.len() as u8;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.campaign_criteria.encrypted_criteria.inner.nonce)
+            .account(
+                ctx.accounts.campaign_criteria.key(),
+                CRITERIA_CIPHERTEXTS_OFFSET,
+                CRITERIA_CIPHERTEXTS_LEN,
+            )
+            .plaintext_u128(audience_estimate.encrypted_count.inner.nonce)
+            .account(
+                audience_estimate.key(),
+                AUDIENCE_COUNT_OFFSET,
+                AUDIENCE_COUNT_LEN,
+            )
+            .plaintext_u32(audience_estimate.profiles_scanned);
+        let args = push_profile_batch(args, &batch).build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![EstimateAudienceCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.audience_estimate.key(),
+                    is_writable: true,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "estimate_audience")]
+    pub fn estimate_audience_callback(
+        ctx: Context<EstimateAudienceCallback>,
+        output: SignedComputationOutputs<EstimateAudienceOutput>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.audience_estimate.pending_computation,
+            ctx.accounts.computation_account.key(),
+            IapError::StaleComputation
+        );
+
+        let audience_estimate = &mut ctx.accounts.audience_estimate;
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(EstimateAudienceOutput { field_0 }) => field_0,
+            Err(_) => {
+                // The batch is dropped and can be counted again
+                audience_estimate.pending_computation = Pubkey::default();
+                audience_estimate.pending_batch_len = 0;
+                emit!(ComputationFailed {
+                    owner: audience_estimate.advertiser,
+                    slot: Clock::get()?.slot,
+                    computation_offset: audience_estimate.pending_computation_offset,
+                    circuit: "estimate_audience".to_string(),
+                });
+                return Ok(());
+            }
+        };
+
+        audience_estimate.encrypted_count = MXEWrapper {
+            inner: MXEEncryptedStruct {
+                nonce: o.nonce,
+                ciphertexts: o.ciphertexts,
+            },
+        };
+        audience_estimate.profiles_scanned += audience_estimate.pending_batch_len as u32;
+        audience_estimate.last_profile = audience_estimate.pending_cursor;
+        audience_estimate.pending_computation = Pubkey::default();
+        audience_estimate.pending_batch_len = 0;
+
+        emit!(AudienceBatchCounted {
+            criteria: audience_estimate.criteria,
+            profiles_scanned: audience_estimate.profiles_scanned,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Reveal the audience count, or only that it is below the k-anonymity floor of
+    // the privacy config. The estimate is final afterwards: scanning more profiles
+    // and revealing again would expose whether the new profiles match.
+    pub fn reveal_audience_estimate(
+        ctx: Context<RevealAudienceEstimate>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let audience_estimate = &ctx.accounts.audience_estimate;
+        require!(!audience_estimate.revealed, IapError::EstimateFinalized);
+        require!(
+            audience_estimate.profiles_scanned > 0,
+            IapError::InvalidProfileBatch
+        );
+        require_keys_eq!(
+            audience_estimate.pending_computation,
+            Pubkey::default(),
+            IapError::EstimateInProgress
+        );
+
+        // Every estimate revealed for the campaign spends from its budget, so re-run
+        // estimates or differently chosen profile sets cannot average the noise away
+        let privacy_config = &ctx.accounts.privacy_config;
+        spend_privacy_budget(
+            &mut ctx.accounts.campaign.privacy_spent_millis,
+            privacy_config.campaign_budget_millis,
+            privacy_config.epsilon_millis,
+        )?;
+
+        let audience_estimate = &mut ctx.accounts.audience_estimate;
+        audience_estimate.pending_computation = ctx.accounts.computation_account.key();
+        audience_estimate.pending_computation_offset = computation_offset;
+        audience_estimate.pending_privacy_millis = privacy_config.epsilon_millis;
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(ctx.accounts.audience_estimate.encrypted_count.inner.nonce)
+            .account(
+                ctx.accounts.audience_estimate.key(),
+                AUDIENCE_COUNT_OFFSET,
+                AUDIENCE_COUNT_LEN,
+            )
+            .plaintext_u32(ctx.accounts.privacy_config.k_anonymity_floor)
+            .plaintext_u64(ctx.accounts.privacy_config.noise_threshold)
+            .build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![RevealAudienceEstimateCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.audience_estimate.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.privacy_config.key(),
+                        is_writable: false,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.campaign.key(),
+                        is_writable: true,
+                    },
+                ],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "reveal_audience_estimate")]
+    pub fn reveal_audience_estimate_callback(
+        ctx: Context<RevealAudienceEstimateCallback>,
+        output: SignedComputationOutputs<RevealAudienceEstimateOutput>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.audience_estimate.pending_computation,
+            ctx.accounts.computation_account.key(),
+            IapError::StaleComputation
+        );
+
+        let (meets_floor, audience_size) = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(RevealAudienceEstimateOutput {
+                field_0:
+                    RevealAudienceEstimateOutputStruct0 {
+                        field_0: meets_floor,
+                        field_1: audience_size,
+                    },
+            }) => (meets_floor, audience_size),
+            Err(_) => {
+                // Nothing was revealed, so the epsilon charged for it is given back
+                let audience_estimate = &mut ctx.accounts.audience_estimate;
+                let campaign = &mut ctx.accounts.campaign;
+                campaign.privacy_spent_millis = campaign
+                    .privacy_spent_millis
+                    .saturating_sub(audience_estimate.pending_privacy_millis);
+                audience_estimate.pending_computation = Pubkey::default();
+                audience_estimate.pending_privacy_millis = 0;
+                emit!(ComputationFailed {
+                    owner: audience_estimate.advertiser,
+                    slot: Clock::get()?.slot,
+                    computation_offset: audience_estimate.pending_computation_offset,
+                    circuit: "reveal_audience_estimate".to_string(),
+                });
+                return Ok(());
+            }
+        };
+
+        let audience_estimate = &mut ctx.accounts.audience_estimate;
+        audience_estimate.pending_computation = Pubkey::default();
+        audience_estimate.pending_privacy_millis = 0;
+        audience_estimate.revealed = true;
+
+        emit!(AudienceEstimated {
+            criteria: audience_estimate.criteria,
+            advertiser: audience_estimate.advertiser,
+            profiles_scanned: audience_estimate.profiles_scanned,
+            k_anonymity_floor: ctx.accounts.privacy_config.k_anonymity_floor,
+            epsilon_millis: ctx.accounts.privacy_config.epsilon_millis,
+            meets_floor,
+            audience_size,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Create the VaultStats account holding the encrypted tier histogram. Restricted
+    // to the vault admin, like every other histogram action.
+    pub fn init_vault_stats(ctx: Context<InitVaultStats>) -> Result<()> {
+        let vault_stats = &mut ctx.accounts.vault_stats;
+        vault_stats.epoch = 1;
+        vault_stats.bump = ctx.bumps.vault_stats;
+        Ok(())
+    }
+
+    // Fold a batch of stored profiles into the encrypted tier histogram. Profiles go
+    // in ascending key order within an epoch, so each one is counted at most once.
+    // Restricted to the vault admin: a public crank could jump the
+    // cursor past most profiles and bias or stall the histogram.
+    pub fn aggregate_tier_histogram(
+        ctx: Context<AggregateTierHistogram>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let vault_stats = &mut ctx.accounts.vault_stats;
+        require_keys_eq!(
+            vault_stats.pending_computation,
+            Pubkey::default(),
+            IapError::StatsInProgress
+        );
+
+        let batch = collect_profile_batch(ctx.remaining_accounts, vault_stats.last_profile)?;
+        vault_stats.pending_computation = ctx.accounts.computation_account.key();
+        vault_stats.pending_computation_offset = computation_offset;
+        vault_stats.pending_cursor = batch[batch.len() - 1].0;
+        vault_stats.pending_batch_len = batch.len() as u8;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(vault_stats.encrypted_histogram.inner.nonce)
+            .account(
+                vault_stats.key(),
+                HISTOGRAM_CIPHERTEXTS_OFFSET,
+                HISTOGRAM_CIPHERTEXTS_LEN,
+            )
+            .plaintext_u32(vault_stats.profiles_scanned);
+        let args = push_profile_batch(args, &batch).build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![AggregateTierHistogramCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[CallbackAccount {
+                    pubkey: ctx.accounts.vault_stats.key(),
+                    is_writable: true,
+                }],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "aggregate_tier_histogram")]
+    pub fn aggregate_tier_histogram_callback(
+        ctx: Context<AggregateTierHistogramCallback>,
+        output: SignedComputationOutputs<AggregateTierHistogramOutput>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.vault_stats.pending_computation,
+            ctx.accounts.computation_account.key(),
+            IapError::StaleComputation
+        );
+
+        let vault_stats = &mut ctx.accounts.vault_stats;
+        let o = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(AggregateTierHistogramOutput { field_0 }) => field_0,
+            Err(_) => {
+                // The batch is dropped and can be counted again
+                vault_stats.pending_computation = Pubkey::default();
+                vault_stats.pending_batch_len = 0;
+                emit!(ComputationFailed {
+                    owner: vault_stats.key(),
+                    slot: Clock::get()?.slot,
+                    computation_offset: vault_stats.pending_computation_offset,
+                    circuit: "aggregate_tier_histogram".to_string(),
+                });
+                return Ok(());
+            }
+        };
+
+        vault_stats.encrypted_histogram = MXEWrapper {
+            inner: MXEEncryptedStruct {
+                nonce: o.nonce,
+                ciphertexts: o.ciphertexts,
+            },
+        };
+        vault_stats.profiles_scanned += vault_stats.pending_batch_len as u32;
+        vault_stats.last_profile = vault_stats.pending_cursor;
+        vault_stats.pending_computation = Pubkey::default();
+        vault_stats.pending_batch_len = 0;
+
+        emit!(TierHistogramAggregated {
+            epoch: vault_stats.epoch,
+            profiles_scanned: vault_stats.profiles_scanned,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Reveal the tier histogram of the current epoch, with every bucket below the
+    // k-anonymity floor reported as 0.
+    pub fn reveal_tier_histogram(
+        ctx: Context<RevealTierHistogram>,
+        computation_offset: u64,
+    ) -> Result<()> {
+        let vault_stats = &mut ctx.accounts.vault_stats;
+        require!(vault_stats.profiles_scanned > 0, IapError::InvalidProfileBatch);
+        require_keys_eq!(
+            vault_stats.pending_computation,
+            Pubkey::default(),
+            IapError::StatsInProgress
+        );
+        let privacy_config = &ctx.accounts.privacy_config;
+        spend_privacy_budget(
+            &mut vault_stats.privacy_spent_millis,
+            privacy_config.stats_budget_millis,
+            privacy_config.epsilon_millis,
+        )?;
+        vault_stats.pending_computation = ctx.accounts.computation_account.key();
+        vault_stats.pending_computation_offset = computation_offset;
+        vault_stats.pending_privacy_millis = privacy_config.epsilon_millis;
+
+        ctx.accounts.sign_pda_account.bump = ctx.bumps.sign_pda_account;
+
+        let args = ArgBuilder::new()
+            .plaintext_u128(vault_stats.encrypted_histogram.inner.nonce)
+            .account(
+                vault_stats.key(),
+                HISTOGRAM_CIPHERTEXTS_OFFSET,
+                HISTOGRAM_CIPHERTEXTS_LEN,
+            )
+            .plaintext_u32(privacy_config.k_anonymity_floor)
+            .plaintext_u64(privacy_config.noise_threshold)
+            .build();
+
+        queue_computation(
+            ctx.accounts,
+            computation_offset,
+            args,
+            None,
+            vec![RevealTierHistogramCallback::callback_ix(
+                computation_offset,
+                &ctx.accounts.mxe_account,
+                &[
+                    CallbackAccount {
+                        pubkey: ctx.accounts.vault_stats.key(),
+                        is_writable: true,
+                    },
+                    CallbackAccount {
+                        pubkey: ctx.accounts.privacy_config.key(),
+                        is_writable: false,
+                    },
+                ],
+            )?],
+            1,
+            0,
+        )?;
+        Ok(())
+    }
+
+    #[arcium_callback(encrypted_ix = "reveal_tier_histogram")]
+    pub fn reveal_tier_histogram_callback(
+        ctx: Context<RevealTierHistogramCallback>,
+        output: SignedComputationOutputs<RevealTierHistogramOutput>,
+    ) -> Result<()> {
+        require_keys_eq!(
+            ctx.accounts.vault_stats.pending_computation,
+            ctx.accounts.computation_account.key(),
+            IapError::StaleComputation
+        );
+
+        let (bronze, silver, gold, platinum) = match output.verify_output(
+            &ctx.accounts.cluster_account,
+            &ctx.accounts.computation_account,
+        ) {
+            Ok(RevealTierHistogramOutput {
+                field_0:
+                    RevealTierHistogramOutputStruct0 {
+                        field_0: bronze,
+                        field_1: silver,
+                        field_2: gold,
+                        field_3: platinum,
+                    },
+            }) => (bronze, silver, gold, platinum),
+            Err(_) => {
+                // Nothing was revealed, so the epsilon charged for it is given back
+                let vault_stats = &mut ctx.accounts.vault_stats;
+                vault_stats.privacy_spent_millis = vault_stats
+                    .privacy_spent_millis
+                    .saturating_sub(vault_stats.pending_privacy_millis);
+                vault_stats.pending_computation = Pubkey::default();
+                vault_stats.pending_privacy_millis = 0;
+                emit!(ComputationFailed {
+                    owner: vault_stats.key(),
+                    slot: Clock::get()?.slot,
+                    computation_offset: vault_stats.pending_computation_offset,
+                    circuit: "reveal_tier_histogram".to_string(),
+                });
+                return Ok(());
+            }
+        };
+
+        let vault_stats = &mut ctx.accounts.vault_stats;
+        vault_stats.pending_computation = Pubkey::default();
+        vault_stats.pending_privacy_millis = 0;
+
+        emit!(TierHistogramRevealed {
+            epoch: vault_stats.epoch,
+            profiles_scanned: vault_stats.profiles_scanned,
+            k_anonymity_floor: ctx.accounts.privacy_config.k_anonymity_floor,
+            epsilon_millis: ctx.accounts.privacy_config.epsilon_millis,
+            privacy_spent_millis: vault_stats.privacy_spent_millis,
+            bronze,
+            silver,
+            gold,
+            platinum,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Start a new histogram epoch: the running histogram is discarded and every
+    // profile can be counted again, picking up tiers that changed since.
+    pub fn reset_vault_stats(ctx: Context<ResetVaultStats>) -> Result<()> {
+        let vault_stats = &mut ctx.accounts.vault_stats;
+        vault_stats.epoch = vault_stats
+            .epoch
+            .checked_add(1)
+            .ok_or(IapError::MathOverflow)?;
+        vault_stats.profiles_scanned = 0;
+        vault_stats.last_profile = Pubkey::default();
+        // privacy_spent_millis is kept: a new epoch reveals the same profiles again
+        // Also drops a computation in flight: its callback no longer matches
+        vault_stats.pending_computation = Pubkey::default();
+        vault_stats.pending_cursor = Pubkey::default();
+        vault_stats.pending_batch_len = 0;
+        vault_stats.pending_privacy_millis = 0;
+        Ok(())
+    }
+
+    // Discard an audience estimate (revealed or not) and refund its rent, e.g. to
+    // start a new scan.
+    pub fn close_audience_estimate(_ctx: Context<CloseAudienceEstimate>) -> Result<()> {
+        Ok(())
+    }
+
+    // Delete the caller's encrypted profile and refund its rent (right to be forgotten).
+    // Pass the owner's PendingProfileSubmission too when a quorum round was ever
+    // opened, so the oracles' ciphertexts are deleted with it, and the owner's
+    // EligibilityConsent accounts as remaining accounts to close them as well. Consents
+    // left open cannot be used on a profile stored later. A computation still in
+    // flight for the profile simply fails its callback, since the account no longer
+    // exists. Claim records go with close_claim_record.
+    pub fn close_interest_profile(ctx: Context<CloseInterestProfile>) -> Result<()> {
+        close_eligibility_consents(
+            ctx.remaining_accounts,
+            &ctx.accounts.user.to_account_info(),
+        )?;
+        emit!(ProfileClosed {
+            owner: ctx.accounts.user_profile.owner,
+            slot: Clock::get()?.slot,
+            schema_version: ctx.accounts.user_profile.version,
+        });
+        Ok(())
+    }
+
+    // Delete a settled ClaimRecord and refund its rent to the claimant. The record is
+    // what prevents a second claim, so this only works once its campaign has ended or
+    // has been closed.
+    pub fn close_claim_record(ctx: Context<CloseClaimRecord>) -> Result<()> {
+        let claim_record = &ctx.accounts.claim_record;
+        require!(
+            claim_record.status != ClaimStatus::Pending,
+            IapError::ClaimRecordInUse
+        );
+        let slot = Clock::get()?.slot;
+        let campaign = &ctx.accounts.campaign;
+        if !campaign.data_is_empty() {
+            require_keys_eq!(*campaign.owner, crate::ID, IapError::ClaimRecordInUse);
+            let campaign = Campaign::try_deserialize(&mut &campaign.try_borrow_data()?[..])?;
+            require!(slot >= campaign.end_slot, IapError::ClaimRecordInUse);
+        }
+
+        emit!(ClaimRecordClosed {
+            campaign: claim_record.campaign,
+            claimant: claim_record.claimant,
+            status: claim_record.status,
+            slot,
+        });
+        Ok(())
+    }
+
+    // Upgrade a UserProfile created under an older layout to the current one.
+    // The account is grown in place (Anchor realloc, new bytes zeroed), which works
+    // because every schema change only appends fields after the existing ones.
+    pub fn migrate_profile(ctx: Context<MigrateProfile>) -> Result<()> {
+        let info = ctx.accounts.user_profile.to_account_info();
+        let mut data = info.try_borrow_mut_data()?;
+        let mut profile = UserProfile::try_deserialize(&mut &data[..])?;
+
+        let from_version = profile.version;
+        require!(
+            from_version < PROFILE_SCHEMA_VERSION,
+            IapError::ProfileAlreadyMigrated
+        );
+
+        let slot = Clock::get()?.slot;
+        profile.owner = ctx.accounts.user.key();
+        profile.version = PROFILE_SCHEMA_VERSION;
+        if from_version == 0 {
+            // Unversioned accounts have no timestamps. An account that never held a
+            // profile stays "never updated" until the next store.
+            profile.created_at = slot;
+            profile.updated_at = if profile.has_encrypted_profile() { slot } else { 0 };
+        }
+        profile.try_serialize(&mut &mut data[..])?;
+
+        emit!(ProfileMigrated {
+            owner: profile.owner,
+            slot,
+            from_version,
+            to_version: PROFILE_SCHEMA_VERSION,
+        });
+        Ok(())
+    }
+
+    // Create the global vault config. Only the program's upgrade authority can do this,
+    // and becomes the vault admin. The tier policy must exist already; the config
+    // points at it so clients can find everything from one account.
+    pub fn initialize_vault(ctx: Context<InitializeVault>, fees: FeeSettings) -> Result<()> {
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.admin = ctx.accounts.admin.key();
+        vault_config.pending_admin = None;
+        vault_config.pause = PauseFlags::default();
+        vault_config.fees = fees;
+        vault_config.tier_policy = ctx.accounts.tier_policy.key();
+        vault_config.bump = ctx.bumps.vault_config;
+
+        emit!(AdminChanged {
+            previous_admin: Pubkey::default(),
+            new_admin: vault_config.admin,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // First step of an admin transfer. The current admin stays in charge until
+    // `new_admin` accepts, so a mistyped key never leaves the vault without an admin;
+    // proposing again replaces the pending proposal.
+    pub fn propose_admin(ctx: Context<UpdateVaultConfig>, new_admin: Pubkey) -> Result<()> {
+        let vault_config = &mut ctx.accounts.vault_config;
+        require!(
+            new_admin != Pubkey::default() && new_admin != vault_config.admin,
+            IapError::InvalidAdmin
+        );
+        vault_config.pending_admin = Some(new_admin);
+
+        emit!(AdminTransferProposed {
+            admin: vault_config.admin,
+            pending_admin: new_admin,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    pub fn cancel_admin_transfer(ctx: Context<UpdateVaultConfig>) -> Result<()> {
+        let vault_config = &mut ctx.accounts.vault_config;
+        let pending_admin = vault_config
+            .pending_admin
+            .take()
+            .ok_or(IapError::NoPendingAdmin)?;
+
+        emit!(AdminTransferCancelled {
+            admin: vault_config.admin,
+            pending_admin,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Second step, signed by the proposed admin. Only needs a signature, so a
+    // multisig vault PDA (e.g. Squads) can accept through its own CPI.
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let vault_config = &mut ctx.accounts.vault_config;
+        let previous_admin = vault_config.admin;
+        vault_config.admin = ctx.accounts.pending_admin.key();
+        vault_config.pending_admin = None;
+
+        emit!(AdminChanged {
+            previous_admin,
+            new_admin: vault_config.admin,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Emergency pause, per instruction family. Callbacks of computations queued
+    // before the pause still complete.
+    pub fn set_pause_flags(ctx: Context<UpdateVaultConfig>, pause: PauseFlags) -> Result<()> {
+        ctx.accounts.vault_config.pause = pause;
+        emit!(VaultPauseChanged {
+            admin: ctx.accounts.admin.key(),
+            store: pause.store,
+            query: pause.query,
+            campaigns: pause.campaigns,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    pub fn update_fee_settings(ctx: Context<UpdateVaultConfig>, fees: FeeSettings) -> Result<()> {
+        ctx.accounts.vault_config.fees = fees;
+        emit!(FeeSettingsUpdated {
+            admin: ctx.accounts.admin.key(),
+            fee_recipient: fees.fee_recipient,
+            store_fee_lamports: fees.store_fee_lamports,
+            query_fee_lamports: fees.query_fee_lamports,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Create the tier policy. Only the program's upgrade authority can do this;
+    // updates are made by the vault admin.
+    pub fn init_tier_policy(
+        ctx: Context<InitTierPolicy>,
+        silver: TierThresholds,
+        gold: TierThresholds,
+        platinum: TierThresholds,
+    ) -> Result<()> {
+        validate_tier_thresholds(&silver, &gold, &platinum)?;
+
+        let tier_policy = &mut ctx.accounts.tier_policy;
+        tier_policy.version = 1;
+        tier_policy.silver = silver;
+        tier_policy.gold = gold;
+        tier_policy.platinum = platinum;
+        tier_policy.bump = ctx.bumps.tier_policy;
+        Ok(())
+    }
+
+    // Replace the thresholds. Every update bumps the policy version, so profiles
+    // stored under an older policy can be found (and re-tiered with compute_tier).
+    pub fn update_tier_policy(
+        ctx: Context<UpdateTierPolicy>,
+        silver: TierThresholds,
+        gold: TierThresholds,
+        platinum: TierThresholds,
+    ) -> Result<()> {
+        validate_tier_thresholds(&silver, &gold, &platinum)?;
+
+        let tier_policy = &mut ctx.accounts.tier_policy;
+        tier_policy.version = tier_policy
+            .version
+            .checked_add(1)
+            .ok_or(IapError::InvalidTierPolicy)?;
+        tier_policy.silver = silver;
+        tier_policy.gold = gold;
+        tier_policy.platinum = platinum;
+        Ok(())
+    }
+
+    // Create the privacy config. Like the tier policy, only the program's upgrade
+    // authority can do this; updates are made by the vault admin.
+    pub fn init_privacy_config(
+        ctx: Context<InitPrivacyConfig>,
+        params: PrivacyParams,
+    ) -> Result<()> {
+        let privacy_config = &mut ctx.accounts.privacy_config;
+        privacy_config.bump = ctx.bumps.privacy_config;
+        privacy_config.apply(&params)
+    }
+
+    // Budgets already spent are kept, so lowering a budget takes effect immediately.
+    pub fn update_privacy_config(
+        ctx: Context<UpdatePrivacyConfig>,
+        params: PrivacyParams,
+    ) -> Result<()> {
+        ctx.accounts.privacy_config.apply(&params)
+    }
+
+    // Create the (empty) registry of profiler oracles allowed to attest stored profiles.
+    // Only the program's upgrade authority can do this; the vault admin manages oracles.
+    pub fn init_oracle_registry(ctx: Context<InitOracleRegistry>) -> Result<()> {
+        let oracle_registry = &mut ctx.accounts.oracle_registry;
+        oracle_registry.next_oracle_id = 1;
+        oracle_registry.oracles = Vec::new();
+        oracle_registry.set_quorum(DEFAULT_QUORUM_THRESHOLD, DEFAULT_QUORUM_SIZE)?;
+        oracle_registry.bump = ctx.bumps.oracle_registry;
+        Ok(())
+    }
+
+    // Set how many oracle submissions a quorum round accepts (quorum_size, N) and how
+    // many it needs before it can be finalized (quorum_threshold, M). Rounds already
+    // open are finalized under the new values.
+    pub fn set_oracle_quorum(
+        ctx: Context<UpdateOracleRegistry>,
+        quorum_threshold: u8,
+        quorum_size: u8,
+    ) -> Result<()> {
+        ctx.accounts
+            .oracle_registry
+            .set_quorum(quorum_threshold, quorum_size)?;
+
+        emit!(OracleQuorumUpdated {
+            quorum_threshold,
+            quorum_size,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Register a profiler oracle. It gets a registry id that stays the same across
+    // key rotations; profiles record that id next to the signing key.
+    pub fn add_oracle(
+        ctx: Context<UpdateOracleRegistry>,
+        oracle: Pubkey,
+        metadata: OracleMetadata,
+    ) -> Result<()> {
+        metadata.validate()?;
+        let oracle_registry = &mut ctx.accounts.oracle_registry;
+        require!(
+            oracle != Pubkey::default() && oracle_registry.find_by_key(&oracle).is_none(),
+            IapError::InvalidOracle
+        );
+        require!(
+            oracle_registry.oracles.len() < MAX_ORACLES,
+            IapError::OracleRegistryFull
+        );
+
+        let oracle_id = oracle_registry.next_oracle_id;
+        oracle_registry.next_oracle_id = oracle_id
+            .checked_add(1)
+            .ok_or(IapError::MathOverflow)?;
+        let slot = Clock::get()?.slot;
+        oracle_registry.oracles.push(OracleEntry {
+            oracle_id,
+            oracle,
+            name: metadata.name.clone(),
+            data_source: metadata.data_source.clone(),
+            min_update_interval_slots: metadata.min_update_interval_slots,
+            added_at: slot,
+        });
+
+        emit!(OracleAdded {
+            oracle_id,
+            oracle,
+            name: metadata.name,
+            data_source: metadata.data_source,
+            min_update_interval_slots: metadata.min_update_interval_slots,
+            slot,
+        });
+        Ok(())
+    }
+
+    // Unregister an oracle. Its signatures are no longer accepted, and every profile it
+    // attested can be flagged stale with flag_stale_profile.
+    pub fn remove_oracle(ctx: Context<UpdateOracleRegistry>, oracle_id: u32) -> Result<()> {
+        let oracle_registry = &mut ctx.accounts.oracle_registry;
+        let index = oracle_registry
+            .oracles
+            .iter()
+            .position(|entry| entry.oracle_id == oracle_id)
+            .ok_or(IapError::UnknownOracle)?;
+        let entry = oracle_registry.oracles.remove(index);
+
+        emit!(OracleRemoved {
+            oracle_id,
+            oracle: entry.oracle,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Replace an oracle's signing key. Profiles attested under the old key stay current,
+    // since they refer to the oracle id, unless `revoke_attestations` is set: the oracle
+    // then gets a fresh id and everything attested under the old one can be flagged
+    // stale, as after a compromise of the old key.
+    pub fn rotate_oracle_key(
+        ctx: Context<UpdateOracleRegistry>,
+        oracle_id: u32,
+        new_oracle: Pubkey,
+        revoke_attestations: bool,
+    ) -> Result<()> {
+        let oracle_registry = &mut ctx.accounts.oracle_registry;
+        require!(
+            new_oracle != Pubkey::default() && oracle_registry.find_by_key(&new_oracle).is_none(),
+            IapError::InvalidOracle
+        );
+        let new_oracle_id = if revoke_attestations {
+            let id = oracle_registry.next_oracle_id;
+            oracle_registry.next_oracle_id = id.checked_add(1).ok_or(IapError::MathOverflow)?;
+            id
+        } else {
+            oracle_id
+        };
+        let entry = oracle_registry
+            .oracles
+            .iter_mut()
+            .find(|entry| entry.oracle_id == oracle_id)
+            .ok_or(IapError::UnknownOracle)?;
+        let old_oracle = entry.oracle;
+        entry.oracle = new_oracle;
+        entry.oracle_id = new_oracle_id;
+
+        emit!(OracleKeyRotated {
+            oracle_id,
+            new_oracle_id,
+            old_oracle,
+            new_oracle,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Permissionless: mark a profile whose attesting oracle id is no longer in the
+    // registry (oracle removed, or its attestations revoked on rotation) as stale.
+    // Stale profiles are not matched, counted or paid out until their owner stores a
+    // profile attested by a registered oracle. Not gated by the pause flags, so a
+    // removed oracle's profiles can be taken out of use during an incident too.
+    pub fn flag_stale_profile(ctx: Context<FlagStaleProfile>) -> Result<()> {
+        let user_profile = &mut ctx.accounts.user_profile;
+        require!(
+            user_profile.oracle_id != 0
+                && !user_profile.stale
+                && ctx
+                    .accounts
+                    .oracle_registry
+                    .find_by_id(user_profile.oracle_id)
+                    .is_none(),
+            IapError::ProfileNotStale
+        );
+        user_profile.stale = true;
+
+        emit!(ProfileFlaggedStale {
+            owner: user_profile.owner,
+            oracle_id: user_profile.oracle_id,
+            attested_by: user_profile.attested_by,
+            slot: Clock::get()?.slot,
+        });
+        Ok(())
+    }
+
+    // Retired: storing a bare tier is no longer supported, since the tier is derived
+    // inside MPC from the full profile. The instruction is kept (with its old arguments)
+    // so existing clients get a clear error instead of a deserialization failure, and it
+    // no longer touches the user_profile PDA.
+    //
+    // The deprecation event is still written to the transaction logs of the failed call,
+    // which lets indexers find clients that need upgrading.
+    #[deprecated(note = "Use store_interest_profile instead")]
+    pub fn store_tier(
+        ctx: Context<StoreTier>,
+        _encrypted_tier: SharedEncryptedStruct<1>
+    ) -> Result<()> {
+        emit!(DeprecatedInstructionCalled {
+            caller: ctx.accounts.user.key(),
+            instruction: "store_tier".to_string(),
+            replacement: "store_interest_profile".to_string(),
+            slot: Clock::get()?.slot,
+        });
+        err!(IapError::StoreTierDeprecated)
+    }
+}
+
+// 4. THE STORAGE STRUCTURE
+#[account]
+pub struct UserProfile {
+    // We use a wrapper because MXEEncryptedStruct does not implement Clone, which #[account] requires.
+    // The wrapper manually implements Clone by copying the public fields.
+    // Updated to store full InterestProfile (6 fields) instead of just tier
+    pub interest_profile: MXEWrapper<PROFILE_FIELD_COUNT>,
+    // Version of the TierPolicy the stored tier was derived under
+    pub tier_policy_version: u32,
+    // Wallet the profile belongs to (the PDA seed)
+    pub owner: Pubkey,
+    // Nonce of the last submitted Enc<Shared, InterestProfile>, to reject nonce reuse
+    pub last_input_nonce: u128,
+    // Computation account of the latest queued computation that writes this profile.
+    // Callbacks of any older computation are rejected as stale.
+    pub pending_computation: Pubkey,
+    // Offset of that computation, reported in profile events
+    pub pending_computation_offset: u64,
+    // Layout version, see PROFILE_SCHEMA_VERSION
+    pub version: u8,
+    // Slot the profile account was created, and slot the encrypted profile was last written
+    pub created_at: u64,
+    pub updated_at: u64,
+    // Oracle that attested the stored profile, and the slot of the wallet snapshot it
+    // was built from. Default / 0 for profiles stored before attestations existed.
+    pub attested_by: Pubkey,
+    pub snapshot_slot: u64,
+    // Attestation of the pending store, applied by its callback
+    pub pending_attested_by: Pubkey,
+    pub pending_snapshot_slot: u64,
+    // Registry id of the attesting oracle (0 = unattested) and of the pending store's
+    pub oracle_id: u32,
+    pub pending_oracle_id: u32,
+    // Set by flag_stale_profile once the attesting oracle was removed from the registry
+    pub stale: bool,
+}
+
+impl UserProfile {
+    // 8 (discriminator) + 16 (nonce) + (32 * 6) (6 ciphertext fields) + 4 (tier_policy_version)
+    // + 32 (owner) + 16 (last_input_nonce) + 32 (pending_computation) + 8 (pending_computation_offset)
+    // + 1 (version) + 8 (created_at) + 8 (updated_at) + 32 (attested_by) + 8 (snapshot_slot)
+    // + 32 (pending_attested_by) + 8 (pending_snapshot_slot) + 4 (oracle_id)
+    // + 4 (pending_oracle_id) + 1 (stale)
+    // New fields must always be appended, so older accounts can be grown by migrate_profile.
+    pub const SPACE: usize = 8 + 16 + (32 * PROFILE_FIELD_COUNT) + 4 + 32 + 16 + 32 + 8 + 1 + 8 + 8
+        + 32 + 8 + 32 + 8 + 4 + 4 + 1;
+
+    fn begin_pending(&mut self, computation_account: Pubkey, computation_offset: u64) {
+        self.pending_computation = computation_account;
+        self.pending_computation_offset = computation_offset;
+    }
+
+    // Only the latest queued computation may write the profile.
+    // Returns the offset of that computation.
+    fn complete_pending(&mut self, computation_account: Pubkey) -> Result<u64> {
+        require_keys_eq!(
+            self.pending_computation,
+            computation_account,
+            IapError::StaleProfile
+        );
+        self.pending_computation = Pubkey::default();
+        Ok(self.pending_computation_offset)
+    }
+
+    // False until the first store_interest_profile callback has written the profile
+    fn has_encrypted_profile(&self) -> bool {
+        self.interest_profile.inner.nonce != 0
+            || self
+                .interest_profile
+                .inner
+                .ciphertexts
+                .iter()
+                .any(|c| *c != [0u8; 32])
+    }
+
+    // Stored, attested by a registered oracle and not flagged stale: the only profiles
+    // queries, claims and aggregates may read. Profiles migrated from before
+    // attestations have oracle_id 0 until their owner stores an attested one.
+    fn require_current(&self) -> Result<()> {
+        require!(self.has_encrypted_profile(), IapError::ProfileNotStored);
+        require!(self.oracle_id != 0, IapError::ProfileNotAttested);
+        require!(!self.stale, IapError::ProfileStale);
+        Ok(())
+    }
+
+    // Writes the result of a pending store (single oracle or quorum) together with
+    // its attestation, and emits ProfileStored or ProfileUpdated
+    fn store_attested_profile(
+        &mut self,
+        profile: MXEEncryptedStruct<PROFILE_FIELD_COUNT>,
+        policy_version: u32,
+        computation_offset: u64,
+        slot: u64,
+    ) {
+        let first_store = !self.has_encrypted_profile();
+        self.set_encrypted_profile(profile);
+        self.tier_policy_version = policy_version;
+        self.updated_at = slot;
+        self.attested_by = self.pending_attested_by;
+        self.snapshot_slot = self.pending_snapshot_slot;
+        self.oracle_id = self.pending_oracle_id;
+        // A fresh attestation replaces the one from a removed oracle
+        self.stale = false;
+
+        if first_store {
+            emit!(ProfileStored {
+                owner: self.owner,
+                slot,
+                schema_version: self.version,
+                computation_offset,
+                tier_policy_version: policy_version,
+                attested_by: self.attested_by,
+                snapshot_slot: self.snapshot_slot,
+            });
+        } else {
+            emit!(ProfileUpdated {
+                owner: self.owner,
+                slot,
+                schema_version: self.version,
+                computation_offset,
+                tier_policy_version: policy_version,
+                attested_by: self.attested_by,
+                snapshot_slot: self.snapshot_slot,
+            });
+        }
+    }
+
+    // Enc<Mxe, InterestProfile>: MXE nonce + 6 ciphertexts
+    fn set_encrypted_profile(&mut self, o: MXEEncryptedStruct<PROFILE_FIELD_COUNT>) {
+        self.interest_profile = MXEWrapper {
+            inner: MXEEncryptedStruct {
+                nonce: o.nonce,
+                ciphertexts: o.ciphertexts,
+            },
+        };
+    }
+}
+
+// An advertiser campaign. Lamport campaigns pay rewards from lamports escrowed in
+// this account on top of its rent-exempt minimum; token campaigns pay from the
+// associated token account of this PDA for `reward_mint`.
+#[account]
+#[derive(InitSpace)]
+pub struct Campaign {
+    pub advertiser: Pubkey,
+    pub campaign_id: u64,
+    // CampaignCriteria account holding the encrypted targeting rules
+    pub criteria: Pubkey,
+    // None for lamport rewards
+    pub reward_mint: Option<Pubkey>,
+    // In lamports, or base units of reward_mint
+    pub reward_per_match: u64,
+    // Claims are accepted in [start_slot, end_slot)
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub max_participants: u32,
+    // Claims paid so far
+    pub participants: u32,
+    // Claims queued in MPC and not settled yet
+    pub pending_claims: u32,
+    pub escrow_balance: u64,
+    // Part of the escrow set aside for pending claims
+    pub reserved_balance: u64,
+    // Epsilon (thousandths) spent on audience estimates revealed for this campaign
+    pub privacy_spent_millis: u32,
+    pub paused: bool,
+    pub bump: u8,
+}
+
+impl Campaign {
+    fn open(
+        &mut self,
+        advertiser: Pubkey,
+        campaign_id: u64,
+        criteria: &Account<CampaignCriteria>,
+        reward_mint: Option<Pubkey>,
+        bump: u8,
+    ) -> Result<()> {
+        require!(criteria.ready, IapError::CriteriaNotReady);
+        self.advertiser = advertiser;
+        self.campaign_id = campaign_id;
+        self.criteria = criteria.key();
+        self.reward_mint = reward_mint;
+        self.participants = 0;
+        self.pending_claims = 0;
+        self.escrow_balance = 0;
+        self.reserved_balance = 0;
+        self.privacy_spent_millis = 0;
+        self.paused = false;
+        self.bump = bump;
+        Ok(())
+    }
+
+    fn set_terms(
+        &mut self,
+        reward_per_match: u64,
+        start_slot: u64,
+        end_slot: u64,
+        max_participants: u32,
+    ) -> Result<()> {
+        require!(
+            reward_per_match > 0 && max_participants > 0,
+            IapError::InvalidCampaignParams
+        );
+        require!(start_slot < end_slot, IapError::InvalidCampaignSchedule);
+        require!(
+            end_slot > Clock::get()?.slot,
+            IapError::InvalidCampaignSchedule
+        );
+        self.reward_per_match = reward_per_match;
+        self.start_slot = start_slot;
+        self.end_slot = end_slot;
+        self.max_participants = max_participants;
+        Ok(())
+    }
+
+    // Running at `slot`, not full, and with escrow left for one more reward
+    fn require_accepting_claims(&self, slot: u64) -> Result<()> {
+        require!(!self.paused, IapError::CampaignPaused);
+        require!(
+            slot >= self.start_slot && slot < self.end_slot,
+            IapError::CampaignNotActive
+        );
+        let claimed_or_pending = self
+            .participants
+            .checked_add(self.pending_claims)
+            .ok_or(IapError::MathOverflow)?;
+        require!(
+            claimed_or_pending < self.max_participants,
+            IapError::CampaignFull
+        );
+        let available = self
+            .escrow_balance
+            .checked_sub(self.reserved_balance)
+            .ok_or(IapError::MathOverflow)?;
+        require!(
+            available >= self.reward_per_match,
+            IapError::InsufficientEscrow
+        );
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, InitSpace)]
+pub enum ClaimStatus {
+    Uninitialized,
+    Pending,
+    Paid,
+    Rejected,
+    Failed,
+}
+
+// One per (campaign, user): its existence is what prevents double claims
+#[account]
+#[derive(InitSpace)]
+pub struct ClaimRecord {
+    pub campaign: Pubkey,
+    pub claimant: Pubkey,
+    // Claimant wallet for lamport campaigns, their token account for token campaigns
+    pub payout_account: Pubkey,
+    pub status: ClaimStatus,
+    pub reward: u64,
+    // Computation account of the eligibility check settling this claim
+    pub computation: Pubkey,
+    pub requested_at: u64,
+    pub bump: u8,
+}
+
+// Per-advertiser limits, created with the advertiser's first criteria
+#[account]
+#[derive(InitSpace)]
+pub struct AdvertiserState {
+    pub advertiser: Pubkey,
+    // Criteria ever stored, capped by MAX_CRITERIA_PER_ADVERTISER
+    pub criteria_count: u32,
+    pub bump: u8,
+}
+
+// Encrypted targeting criteria of an advertiser, see store_campaign_criteria
+#[account]
+pub struct CampaignCriteria {
+    // Enc<Mxe, CampaignCriteria>, kept first so circuits can read it by offset
+    pub encrypted_criteria: MXEWrapper<CRITERIA_FIELD_COUNT>,
+    pub advertiser: Pubkey,
+    pub criteria_id: u64,
+    // Computation account of the store computation, cleared by its callback
+    pub pending_computation: Pubkey,
+    pub pending_computation_offset: u64,
+    // Set once the MXE-encrypted criteria have been written
+    pub ready: bool,
+    pub bump: u8,
+}
+
+impl CampaignCriteria {
+    // 8 (discriminator) + 16 (nonce) + (32 * 6) (ciphertexts) + 32 (advertiser)
+    // + 8 (criteria_id) + 32 (pending_computation) + 8 (pending_computation_offset)
+    // + 1 (ready) + 1 (bump)
+    pub const SPACE: usize = 8 + 16 + (32 * CRITERIA_FIELD_COUNT) + 32 + 8 + 32 + 8 + 1 + 1;
+}
+
+// Profiles submitted by an oracle quorum for `owner`, see submit_oracle_profile
+#[account]
+pub struct PendingProfileSubmission {
+    // Enc<Shared, InterestProfile> of each submission, kept first so circuits can read
+    // them by offset; each under the oracle's encryption key and nonce below
+    pub ciphertexts: [[[u8; 32]; PROFILE_FIELD_COUNT]; MAX_QUORUM_SUBMISSIONS],
+    pub encryption_keys: [[u8; 32]; MAX_QUORUM_SUBMISSIONS],
+    pub nonces: [u128; MAX_QUORUM_SUBMISSIONS],
+    pub oracle_ids: [u32; MAX_QUORUM_SUBMISSIONS],
+    pub snapshot_slots: [u64; MAX_QUORUM_SUBMISSIONS],
+    pub owner: Pubkey,
+    // Set by the owner in open_quorum_round, cleared once the round is stored
+    pub round_open: bool,
+    // Submissions in the current round
+    pub count: u8,
+    pub round_started_at: u64,
+    // Largest min_update_interval_slots among the round's oracles
+    pub min_update_interval_slots: u64,
+    // finalize_oracle_quorum computation in flight, cleared by its callback
+    pub pending_computation: Pubkey,
+    pub bump: u8,
+}
+
+impl PendingProfileSubmission {
+    // 8 (discriminator) + (32 * 6 * 5) (ciphertexts) + (32 * 5) (encryption_keys)
+    // + (16 * 5) (nonces) + (4 * 5) (oracle_ids) + (8 * 5) (snapshot_slots) + 32 (owner)
+    // + 1 (round_open) + 1 (count) + 8 (round_started_at) + 8 (min_update_interval_slots)
+    // + 32 (pending_computation) + 1 (bump)
+    pub const SPACE: usize = 8
+        + (32 * PROFILE_FIELD_COUNT * MAX_QUORUM_SUBMISSIONS)
+        + (32 * MAX_QUORUM_SUBMISSIONS)
+        + (16 * MAX_QUORUM_SUBMISSIONS)
+        + (4 * MAX_QUORUM_SUBMISSIONS)
+        + (8 * MAX_QUORUM_SUBMISSIONS)
+        + 32
+        + 1
+        + 1
+        + 8
+        + 8
+        + 32
+        + 1;
+}
+
+// Running audience count for one CampaignCriteria, see estimate_audience
+#[account]
+pub struct AudienceEstimate {
+    // Enc<Mxe, u32>, kept first so circuits can read it by offset
+    pub encrypted_count: MXEWrapper<1>,
+    pub criteria: Pubkey,
+    pub advertiser: Pubkey,
+    pub profiles_scanned: u32,
+    // Highest profile key counted so far; batches must continue above it
+    pub last_profile: Pubkey,
+    // Computation in flight and the batch it will apply, cleared by its callback
+    pub pending_computation: Pubkey,
+    pub pending_computation_offset: u64,
+    pub pending_cursor: Pubkey,
+    pub pending_batch_len: u8,
+    // Epsilon (thousandths) charged by the reveal in flight, refunded if it fails
+    pub pending_privacy_millis: u32,
+    // Set by reveal_audience_estimate, after which the estimate cannot grow
+    pub revealed: bool,
+    pub bump: u8,
+}
+
+impl AudienceEstimate {
+    // 8 (discriminator) + 16 (nonce) + 32 (ciphertext) + 32 (criteria) + 32 (advertiser)
+    // + 4 (profiles_scanned) + 32 (last_profile) + 32 (pending_computation)
+    // + 8 (pending_computation_offset) + 32 (pending_cursor) + 1 (pending_batch_len)
+    // + 4 (pending_privacy_millis) + 1 (revealed) + 1 (bump)
+    pub const SPACE: usize = 8 + 16 + 32 + 32 + 32 + 4 + 32 + 32 + 8 + 32 + 1 + 4 + 1 + 1;
+}
+
+// Encrypted tier histogram over all profiles, built batch by batch per epoch
+#[account]
+pub struct VaultStats {
+    // Enc<Mxe, TierHistogram>, kept first so circuits can read it by offset
+    pub encrypted_histogram: MXEWrapper<TIER_COUNT>,
+    pub epoch: u32,
+    pub profiles_scanned: u32,
+    // Highest profile key counted in this epoch; batches must continue above it
+    pub last_profile: Pubkey,
+    // Computation in flight and the batch it will apply, cleared by its callback
+    pub pending_computation: Pubkey,
+    pub pending_computation_offset: u64,
+    pub pending_cursor: Pubkey,
+    pub pending_batch_len: u8,
+    // Epsilon (thousandths) charged by the reveal in flight, refunded if it fails
+    pub pending_privacy_millis: u32,
+    // Epsilon (thousandths) spent on reveals over all epochs
+    pub privacy_spent_millis: u32,
+    pub bump: u8,
+}
+
+impl VaultStats {
+    // 8 (discriminator) + 16 (nonce) + (32 * 4) (ciphertexts) + 4 (epoch)
+    // + 4 (profiles_scanned) + 32 (last_profile) + 32 (pending_computation)
+    // + 8 (pending_computation_offset) + 32 (pending_cursor) + 1 (pending_batch_len)
+    // + 4 (pending_privacy_millis) + 4 (privacy_spent_millis) + 1 (bump)
+    pub const SPACE: usize = 8 + 16 + (32 * TIER_COUNT) + 4 + 4 + 32 + 32 + 8 + 32 + 1 + 4 + 4 + 1;
+}
+
+// Admin-managed limits on what aggregate computations may reveal
+#[account]
+#[derive(InitSpace)]
+pub struct PrivacyConfig {
+    // Aggregate counts below this are never revealed
+    pub k_anonymity_floor: u32,
+    // Differential privacy epsilon per reveal, in thousandths
+    pub epsilon_millis: u32,
+    // exp(-epsilon) * 2^32, passed to the noise circuits
+    pub noise_threshold: u64,
+    // Total epsilon (thousandths) that may ever be spent per campaign, and on the
+    // tier histogram
+    pub campaign_budget_millis: u32,
+    pub stats_budget_millis: u32,
+    pub bump: u8,
+}
+
+impl PrivacyConfig {
+    fn apply(&mut self, params: &PrivacyParams) -> Result<()> {
+        require!(
+            params.k_anonymity_floor >= MIN_K_ANONYMITY_FLOOR,
+            IapError::InvalidPrivacyConfig
+        );
+        require!(
+            (MIN_EPSILON_MILLIS..=MAX_EPSILON_MILLIS).contains(&params.epsilon_millis),
+            IapError::InvalidPrivacyConfig
+        );
+        // A budget smaller than epsilon would allow no reveal at all
+        require!(
+            params.campaign_budget_millis >= params.epsilon_millis
+                && params.stats_budget_millis >= params.epsilon_millis,
+            IapError::InvalidPrivacyConfig
+        );
+        self.k_anonymity_floor = params.k_anonymity_floor;
+        self.epsilon_millis = params.epsilon_millis;
+        self.noise_threshold = noise_threshold(params.epsilon_millis);
+        self.campaign_budget_millis = params.campaign_budget_millis;
+        self.stats_budget_millis = params.stats_budget_millis;
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct PrivacyParams {
+    pub k_anonymity_floor: u32,
+    pub epsilon_millis: u32,
+    pub campaign_budget_millis: u32,
+    pub stats_budget_millis: u32,
+}
+
+// Plaintext criteria for check_eligibility. Each field is a minimum the stored
+// profile must reach, in the same order as InterestProfileTuple.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct EligibilityCriteria {
+    pub min_tier: u8,
+    pub min_nft_count: u32,
+    pub min_sol_lamports: u64,
+    pub min_trading_volume_cents: u64,
+    pub min_token_holdings: u32,
+    pub min_defi_interactions: u32,
+}
+
+// An owner's permission for one requester to run check_eligibility, or for one
+// advertiser to run match_campaign_criteria, on their profile
+#[account]
+#[derive(InitSpace)]
+pub struct EligibilityConsent {
+    pub owner: Pubkey,
+    pub requester: Pubkey,
+    // Checks left, each one reveals a bit about the profile
+    pub queries_remaining: u32,
+    // Last slot at which a check can be queued
+    pub expires_at_slot: u64,
+    pub bump: u8,
+    // created_at of the profile the consent was granted on. A profile deleted and
+    // stored again does not inherit consents left open on the old one.
+    pub profile_created_at: u64,
+}
+
+impl EligibilityConsent {
+    fn use_query(&mut self, profile: &UserProfile, slot: u64) -> Result<()> {
+        require!(
+            self.profile_created_at == profile.created_at,
+            IapError::ConsentMismatch
+        );
+        require!(
+            self.queries_remaining > 0 && slot <= self.expires_at_slot,
+            IapError::ConsentExhausted
+        );
+        self.queries_remaining -= 1;
+        Ok(())
+    }
+}
+
+// Tier thresholds consumed by the tier-derivation circuits (plaintext inputs).
+// A wallet reaches a tier when it is strictly above the NFT and SOL thresholds
+// and at least at the trading volume and DeFi minimums.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct TierThresholds {
+    pub nft_count_above: u32,
+    pub sol_lamports_above: u64,
+    pub min_trading_volume_cents: u64,
+    pub min_defi_interactions: u32,
+}
+
+// Global vault state. Every user-facing instruction checks the pause flag of its
+// family: store (writing profiles), query (anything computing on stored profiles)
+// and campaigns (criteria, funding and claims). Closing one's own accounts (profiles
+// and campaigns, so escrowed funds can always be withdrawn), migrating a profile,
+// instructions that only stop activity (pausing a campaign, revoking a consent,
+// flagging a stale profile, expiring a claim), admin instructions and callbacks are
+// never paused.
+#[account]
+#[derive(InitSpace)]
+pub struct VaultConfig {
+    // Also manages the tier policy, privacy config, oracle registry and vault stats
+    pub admin: Pubkey,
+    // Proposed by propose_admin, becomes admin once it signs accept_admin
+    pub pending_admin: Option<Pubkey>,
+    pub pause: PauseFlags,
+    pub fees: FeeSettings,
+    // The TierPolicy account the tier-derivation circuits use
+    pub tier_policy: Pubkey,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct PauseFlags {
+    pub store: bool,
+    pub query: bool,
+    pub campaigns: bool,
+}
+
+// Flat lamport fees, paid to fee_recipient by the user storing a profile
+// (store_interest_profile) and by whoever queries one (check_eligibility,
+// match_campaign_criteria). fee_recipient must be a rent-exempt account.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, InitSpace)]
+pub struct FeeSettings {
+    pub fee_recipient: Pubkey,
+    pub store_fee_lamports: u64,
+    pub query_fee_lamports: u64,
+}
+
+// Profiler oracles whose ed25519 signatures store_interest_profile accepts
+#[account]
+#[derive(InitSpace)]
+pub struct OracleRegistry {
+    // Id given to the next added oracle. Ids are never reused, so a profile attested
+    // by a removed oracle can't become current again.
+    pub next_oracle_id: u32,
+    #[max_len(MAX_ORACLES)]
+    pub oracles: Vec<OracleEntry>,
+    // Submissions a quorum round needs before it can be finalized (M), out of the
+    // submissions it accepts (N)
+    pub quorum_threshold: u8,
+    pub quorum_size: u8,
+    pub bump: u8,
+}
+
+impl OracleRegistry {
+    // 1 <= M <= N <= MAX_QUORUM_SUBMISSIONS
+    fn set_quorum(&mut self, quorum_threshold: u8, quorum_size: u8) -> Result<()> {
+        require!(
+            quorum_threshold >= 1
+                && quorum_threshold <= quorum_size
+                && usize::from(quorum_size) <= MAX_QUORUM_SUBMISSIONS,
+            IapError::InvalidQuorumConfig
+        );
+        self.quorum_threshold = quorum_threshold;
+        self.quorum_size = quorum_size;
+        Ok(())
+    }
+
+    fn find_by_key(&self, oracle: &Pubkey) -> Option<&OracleEntry> {
+        self.oracles.iter().find(|entry| entry.oracle == *oracle)
+    }
+
+    fn find_by_id(&self, oracle_id: u32) -> Option<&OracleEntry> {
+        self.oracles.iter().find(|entry| entry.oracle_id == oracle_id)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct OracleEntry {
+    pub oracle_id: u32,
+    // Current ed25519 signing key
+    pub oracle: Pubkey,
+    #[max_len(MAX_ORACLE_NAME_LEN)]
+    pub name: String,
+    // Where the oracle reads wallet data from, e.g. "helius"
+    #[max_len(MAX_ORACLE_DATA_SOURCE_LEN)]
+    pub data_source: String,
+    // Max update frequency: snapshots of one wallet must be at least this many slots apart
+    pub min_update_interval_slots: u64,
+    pub added_at: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct OracleMetadata {
+    pub name: String,
+    pub data_source: String,
+    pub min_update_interval_slots: u64,
+}
+
+impl OracleMetadata {
+    fn validate(&self) -> Result<()> {
+        require!(
+            !self.name.is_empty()
+                && self.name.len() <= MAX_ORACLE_NAME_LEN
+                && !self.data_source.is_empty()
+                && self.data_source.len() <= MAX_ORACLE_DATA_SOURCE_LEN,
+            IapError::InvalidOracleMetadata
+        );
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct TierPolicy {
+    // Incremented on every update and recorded in each UserProfile
+    pub version: u32,
+    pub silver: TierThresholds,
+    pub gold: TierThresholds,
+    pub platinum: TierThresholds,
+    pub bump: u8,
+}
+
+// 5. THE CONTEXT
+#[queue_computation_accounts("store_interest_profile", user)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct StoreInterestProfile<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.store @ IapError::StorePaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+    #[account(mut, address = vault_config.fees.fee_recipient @ IapError::InvalidFeeRecipient)]
+    /// CHECK: only receives the fee, checked against the vault config
+    pub fee_recipient: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserProfile::SPACE,
+        seeds = [USER_PROFILE_SEED, user.key().as_ref()],
+        bump
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(seeds = [TIER_POLICY_SEED], bump = tier_policy.bump)]
+    pub tier_policy: Box<Account<'info, TierPolicy>>,
+
+    #[account(seeds = [ORACLE_REGISTRY_SEED], bump = oracle_registry.bump)]
+    pub oracle_registry: Box<Account<'info, OracleRegistry>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_STORE_INTEREST_PROFILE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("store_interest_profile")]
+#[derive(Accounts)]
+pub struct StoreInterestProfileCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_STORE_INTEREST_PROFILE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as a writable CallbackAccount by store_interest_profile
+    #[account(mut)]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+}
+
+#[queue_computation_accounts("compute_tier", user)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct ComputeTier<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.store @ IapError::StorePaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        mut,
+        seeds = [USER_PROFILE_SEED, user_profile.owner.as_ref()],
+        bump,
+        constraint = user_profile.owner == user.key() @ IapError::UnauthorizedUpdater
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(seeds = [TIER_POLICY_SEED], bump = tier_policy.bump)]
+    pub tier_policy: Box<Account<'info, TierPolicy>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_COMPUTE_TIER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("compute_tier")]
+#[derive(Accounts)]
+pub struct ComputeTierCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_COMPUTE_TIER))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as a writable CallbackAccount by compute_tier
+    #[account(mut)]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+}
+
+#[queue_computation_accounts("check_eligibility", requester)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct CheckEligibility<'info> {
+    #[account(mut)]
+    pub requester: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.query @ IapError::QueryPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+    #[account(mut, address = vault_config.fees.fee_recipient @ IapError::InvalidFeeRecipient)]
+    /// CHECK: only receives the fee, checked against the vault config
+    pub fee_recipient: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [USER_PROFILE_SEED, user_profile.owner.as_ref()],
+        bump
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(
+        mut,
+        seeds = [ELIGIBILITY_CONSENT_SEED, user_profile.owner.as_ref(), requester.key().as_ref()],
+        bump = eligibility_consent.bump
+    )]
+    pub eligibility_consent: Box<Account<'info, EligibilityConsent>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = requester,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_ELIGIBILITY))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("check_eligibility")]
+#[derive(Accounts)]
+pub struct CheckEligibilityCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CHECK_ELIGIBILITY))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as read-only CallbackAccounts by check_eligibility
+    pub user_profile: Box<Account<'info, UserProfile>>,
+    /// CHECK: only used to report who asked, in the EligibilityChecked event
+    pub requester: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(requester: Pubkey)]
+pub struct GrantEligibilityConsent<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.query @ IapError::QueryPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        seeds = [USER_PROFILE_SEED, user.key().as_ref()],
+        bump,
+        constraint = user_profile.owner == user.key() @ IapError::UnauthorizedUpdater
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + EligibilityConsent::INIT_SPACE,
+        seeds = [ELIGIBILITY_CONSENT_SEED, user.key().as_ref(), requester.as_ref()],
+        bump
+    )]
+    pub eligibility_consent: Box<Account<'info, EligibilityConsent>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeEligibilityConsent<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [ELIGIBILITY_CONSENT_SEED, user.key().as_ref(), eligibility_consent.requester.as_ref()],
+        bump = eligibility_consent.bump
+    )]
+    pub eligibility_consent: Box<Account<'info, EligibilityConsent>>,
+}
+
+#[queue_computation_accounts("read_my_profile", user)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct ReadMyProfile<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.query @ IapError::QueryPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        seeds = [USER_PROFILE_SEED, user.key().as_ref()],
+        bump,
+        constraint = user_profile.owner == user.key() @ IapError::UnauthorizedUpdater
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_READ_MY_PROFILE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("read_my_profile")]
+#[derive(Accounts)]
+pub struct ReadMyProfileCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_READ_MY_PROFILE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as a read-only CallbackAccount by read_my_profile
+    pub user_profile: Box<Account<'info, UserProfile>>,
+}
+
+#[queue_computation_accounts("disclose_fields", user)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct DiscloseFields<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.query @ IapError::QueryPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        seeds = [USER_PROFILE_SEED, user.key().as_ref()],
+        bump,
+        constraint = user_profile.owner == user.key() @ IapError::UnauthorizedUpdater
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_DISCLOSE_FIELDS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("disclose_fields")]
+#[derive(Accounts)]
+pub struct DiscloseFieldsCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_DISCLOSE_FIELDS))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as a read-only CallbackAccount by disclose_fields
+    pub user_profile: Box<Account<'info, UserProfile>>,
+}
+
+#[queue_computation_accounts("store_campaign_criteria", advertiser)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64, criteria_id: u64)]
+pub struct StoreCampaignCriteria<'info> {
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.campaigns @ IapError::CampaignsPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        init_if_needed,
+        payer = advertiser,
+        space = CampaignCriteria::SPACE,
+        seeds = [CAMPAIGN_CRITERIA_SEED, advertiser.key().as_ref(), &criteria_id.to_le_bytes()],
+        bump
+    )]
+    pub campaign_criteria: Box<Account<'info, CampaignCriteria>>,
+
+    #[account(
+        init_if_needed,
+        payer = advertiser,
+        space = 8 + AdvertiserState::INIT_SPACE,
+        seeds = [ADVERTISER_STATE_SEED, advertiser.key().as_ref()],
+        bump
+    )]
+    pub advertiser_state: Box<Account<'info, AdvertiserState>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = advertiser,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_STORE_CAMPAIGN_CRITERIA))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("store_campaign_criteria")]
+#[derive(Accounts)]
+pub struct StoreCampaignCriteriaCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_STORE_CAMPAIGN_CRITERIA))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as a writable CallbackAccount by store_campaign_criteria
+    #[account(mut)]
+    pub campaign_criteria: Box<Account<'info, CampaignCriteria>>,
+}
+
+#[queue_computation_accounts("match_campaign_criteria", advertiser)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct MatchCampaignCriteria<'info> {
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.query @ IapError::QueryPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+    #[account(mut, address = vault_config.fees.fee_recipient @ IapError::InvalidFeeRecipient)]
+    /// CHECK: only receives the fee, checked against the vault config
+    pub fee_recipient: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [USER_PROFILE_SEED, user_profile.owner.as_ref()],
+        bump
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(
+        mut,
+        seeds = [ELIGIBILITY_CONSENT_SEED, user_profile.owner.as_ref(), advertiser.key().as_ref()],
+        bump = eligibility_consent.bump
+    )]
+    pub eligibility_consent: Box<Account<'info, EligibilityConsent>>,
+
+    #[account(
+        seeds = [CAMPAIGN_SEED, advertiser.key().as_ref(), &campaign.campaign_id.to_le_bytes()],
+        bump = campaign.bump
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    #[account(
+        address = campaign.criteria,
+        has_one = advertiser @ IapError::Unauthorized
+    )]
+    pub campaign_criteria: Box<Account<'info, CampaignCriteria>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = advertiser,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_CAMPAIGN_CRITERIA))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("match_campaign_criteria")]
+#[derive(Accounts)]
+pub struct MatchCampaignCriteriaCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MATCH_CAMPAIGN_CRITERIA))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as read-only CallbackAccounts by match_campaign_criteria
+    pub user_profile: Box<Account<'info, UserProfile>>,
+    pub campaign_criteria: Box<Account<'info, CampaignCriteria>>,
+}
+
+#[derive(Accounts)]
+#[instruction(campaign_id: u64)]
+pub struct CreateCampaign<'info> {
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.campaigns @ IapError::CampaignsPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        init,
+        payer = advertiser,
+        space = 8 + Campaign::INIT_SPACE,
+        seeds = [CAMPAIGN_SEED, advertiser.key().as_ref(), &campaign_id.to_le_bytes()],
+        bump
+    )]
+    pub campaign: Account<'info, Campaign>,
+
+    #[account(has_one = advertiser @ IapError::Unauthorized)]
+    pub campaign_criteria: Box<Account<'info, CampaignCriteria>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundCampaign<'info> {
+    #[account(mut)]
+    pub funder: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.campaigns @ IapError::CampaignsPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        mut,
+        seeds = [CAMPAIGN_SEED, campaign.advertiser.as_ref(), &campaign.campaign_id.to_le_bytes()],
+        bump = campaign.bump
+    )]
+    pub campaign: Account<'info, Campaign>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCampaign<'info> {
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [CAMPAIGN_SEED, advertiser.key().as_ref(), &campaign.campaign_id.to_le_bytes()],
+        bump = campaign.bump,
+        has_one = advertiser @ IapError::Unauthorized
+    )]
+    pub campaign: Account<'info, Campaign>,
+}
+
+#[derive(Accounts)]
+pub struct ResumeCampaign<'info> {
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.campaigns @ IapError::CampaignsPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        mut,
+        seeds = [CAMPAIGN_SEED, advertiser.key().as_ref(), &campaign.campaign_id.to_le_bytes()],
+        bump = campaign.bump,
+        has_one = advertiser @ IapError::Unauthorized
+    )]
+    pub campaign: Account<'info, Campaign>,
+}
+
+#[derive(Accounts)]
+pub struct CloseCampaign<'info> {
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        mut,
+        close = advertiser,
+        seeds = [CAMPAIGN_SEED, advertiser.key().as_ref(), &campaign.campaign_id.to_le_bytes()],
+        bump = campaign.bump,
+        has_one = advertiser @ IapError::Unauthorized
+    )]
+    pub campaign: Account<'info, Campaign>,
+}
+
+#[derive(Accounts)]
+#[instruction(campaign_id: u64)]
+pub struct CreateTokenCampaign<'info> {
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.campaigns @ IapError::CampaignsPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        init,
+        payer = advertiser,
+        space = 8 + Campaign::INIT_SPACE,
+        seeds = [CAMPAIGN_SEED, advertiser.key().as_ref(), &campaign_id.to_le_bytes()],
+        bump
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    #[account(has_one = advertiser @ IapError::Unauthorized)]
+    pub campaign_criteria: Box<Account<'info, CampaignCriteria>>,
+
+    #[account(mint::token_program = token_program)]
+    pub reward_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        init,
+        payer = advertiser,
+        associated_token::mint = reward_mint,
+        associated_token::authority = campaign,
+        associated_token::token_program = token_program
+    )]
+    pub escrow_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = advertiser,
+        token::token_program = token_program
+    )]
+    pub advertiser_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundTokenCampaign<'info> {
+    pub funder: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.campaigns @ IapError::CampaignsPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        mut,
+        seeds = [CAMPAIGN_SEED, campaign.advertiser.as_ref(), &campaign.campaign_id.to_le_bytes()],
+        bump = campaign.bump,
+        constraint = campaign.reward_mint == Some(reward_mint.key()) @ IapError::RewardMintMismatch
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    pub reward_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = campaign,
+        associated_token::token_program = token_program
+    )]
+    pub escrow_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = funder,
+        token::token_program = token_program
+    )]
+    pub funder_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTokenCampaign<'info> {
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        mut,
+        close = advertiser,
+        seeds = [CAMPAIGN_SEED, advertiser.key().as_ref(), &campaign.campaign_id.to_le_bytes()],
+        bump = campaign.bump,
+        has_one = advertiser @ IapError::Unauthorized,
+        constraint = campaign.reward_mint == Some(reward_mint.key()) @ IapError::RewardMintMismatch
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    // Writable for harvesting withheld transfer fees before the vault is closed
+    #[account(mut)]
+    pub reward_mint: Box<InterfaceAccount<'info, Mint>>,
+
+    #[account(
+        mut,
+        associated_token::mint = reward_mint,
+        associated_token::authority = campaign,
+        associated_token::token_program = token_program
+    )]
+    pub escrow_vault: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::token_program = token_program
+    )]
+    pub advertiser_token_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[queue_computation_accounts("claim_campaign_reward", user)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct ClaimCampaignReward<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.campaigns @ IapError::CampaignsPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        seeds = [USER_PROFILE_SEED, user.key().as_ref()],
+        bump,
+        constraint = user_profile.owner == user.key() @ IapError::UnauthorizedUpdater
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(
+        mut,
+        seeds = [CAMPAIGN_SEED, campaign.advertiser.as_ref(), &campaign.campaign_id.to_le_bytes()],
+        bump = campaign.bump
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    #[account(address = campaign.criteria)]
+    pub campaign_criteria: Box<Account<'info, CampaignCriteria>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = 8 + ClaimRecord::INIT_SPACE,
+        seeds = [CLAIM_RECORD_SEED, campaign.key().as_ref(), user.key().as_ref()],
+        bump
+    )]
+    pub claim_record: Box<Account<'info, ClaimRecord>>,
+
+    // Token campaigns only, see token_payout_accounts
+    pub reward_mint: Option<Box<InterfaceAccount<'info, Mint>>>,
+    pub escrow_vault: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub claimant_token_account: Option<Box<InterfaceAccount<'info, TokenAccount>>>,
+    pub token_program: Option<Interface<'info, TokenInterface>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = user,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CLAIM_CAMPAIGN_REWARD))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+impl<'info> ClaimCampaignReward<'info> {
+    // Checks the token accounts of a token campaign claim. Returns the account the
+    // reward is paid to and the accounts the callback needs for the transfer.
+    fn token_payout_accounts(&self, mint: Pubkey) -> Result<(Pubkey, Vec<CallbackAccount>)> {
+        let (Some(reward_mint), Some(escrow_vault), Some(claimant_token_account), Some(token_program)) = (
+            self.reward_mint.as_ref(),
+            self.escrow_vault.as_ref(),
+            self.claimant_token_account.as_ref(),
+            self.token_program.as_ref(),
+        ) else {
+            return err!(IapError::MissingTokenAccounts);
+        };
+
+        require_keys_eq!(reward_mint.key(), mint, IapError::RewardMintMismatch);
+        require_keys_eq!(
+            *reward_mint.to_account_info().owner,
+            token_program.key(),
+            IapError::RewardMintMismatch
+        );
+        require_keys_eq!(
+            escrow_vault.key(),
+            get_associated_token_address_with_program_id(
+                &self.campaign.key(),
+                &mint,
+                &token_program.key()
+            ),
+            IapError::InvalidEscrowVault
+        );
+        require_keys_eq!(claimant_token_account.mint, mint, IapError::RewardMintMismatch);
+        require_keys_eq!(
+            claimant_token_account.owner,
+            self.user.key(),
+            IapError::Unauthorized
+        );
+
+        Ok((
+            claimant_token_account.key(),
+            vec![
+                CallbackAccount {
+                    pubkey: mint,
+                    is_writable: false,
+                },
+                CallbackAccount {
+                    pubkey: escrow_vault.key(),
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: claimant_token_account.key(),
+                    is_writable: true,
+                },
+                CallbackAccount {
+                    pubkey: token_program.key(),
+                    is_writable: false,
+                },
+            ],
+        ))
+    }
+}
+
+#[callback_accounts("claim_campaign_reward")]
+#[derive(Accounts)]
+pub struct ClaimCampaignRewardCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_CLAIM_CAMPAIGN_REWARD))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as writable CallbackAccounts by claim_campaign_reward
+    #[account(mut)]
+    pub campaign: Box<Account<'info, Campaign>>,
+    #[account(
+        mut,
+        has_one = campaign,
+        has_one = claimant
+    )]
+    pub claim_record: Box<Account<'info, ClaimRecord>>,
+    /// CHECK: receives the reward, checked against claim_record.claimant
+    #[account(mut)]
+    pub claimant: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpirePendingClaim<'info> {
+    #[account(mut, address = claim_record.campaign)]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    #[account(
+        mut,
+        seeds = [CLAIM_RECORD_SEED, claim_record.campaign.as_ref(), claim_record.claimant.as_ref()],
+        bump = claim_record.bump
+    )]
+    pub claim_record: Box<Account<'info, ClaimRecord>>,
+}
+
+#[queue_computation_accounts("estimate_audience", advertiser)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct EstimateAudience<'info> {
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.query @ IapError::QueryPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(has_one = advertiser @ IapError::Unauthorized)]
+    pub campaign_criteria: Box<Account<'info, CampaignCriteria>>,
+
+    #[account(
+        init_if_needed,
+        payer = advertiser,
+        space = AudienceEstimate::SPACE,
+        seeds = [AUDIENCE_ESTIMATE_SEED, campaign_criteria.key().as_ref()],
+        bump
+    )]
+    pub audience_estimate: Box<Account<'info, AudienceEstimate>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = advertiser,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_ESTIMATE_AUDIENCE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("estimate_audience")]
+#[derive(Accounts)]
+pub struct EstimateAudienceCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_ESTIMATE_AUDIENCE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as a writable CallbackAccount by estimate_audience
+    #[account(mut)]
+    pub audience_estimate: Box<Account<'info, AudienceEstimate>>,
+}
+
+#[queue_computation_accounts("reveal_audience_estimate", advertiser)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RevealAudienceEstimate<'info> {
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.query @ IapError::QueryPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        mut,
+        seeds = [AUDIENCE_ESTIMATE_SEED, audience_estimate.criteria.as_ref()],
+        bump = audience_estimate.bump,
+        has_one = advertiser @ IapError::Unauthorized
+    )]
+    pub audience_estimate: Box<Account<'info, AudienceEstimate>>,
+
+    // Campaign the estimate is revealed for, charged with its epsilon
+    #[account(
+        mut,
+        seeds = [CAMPAIGN_SEED, advertiser.key().as_ref(), &campaign.campaign_id.to_le_bytes()],
+        bump = campaign.bump,
+        constraint = campaign.criteria == audience_estimate.criteria @ IapError::CriteriaMismatch
+    )]
+    pub campaign: Box<Account<'info, Campaign>>,
+
+    #[account(seeds = [PRIVACY_CONFIG_SEED], bump = privacy_config.bump)]
+    pub privacy_config: Box<Account<'info, PrivacyConfig>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = advertiser,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_AUDIENCE_ESTIMATE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("reveal_audience_estimate")]
+#[derive(Accounts)]
+pub struct RevealAudienceEstimateCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_AUDIENCE_ESTIMATE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as CallbackAccounts by reveal_audience_estimate
+    #[account(mut)]
+    pub audience_estimate: Box<Account<'info, AudienceEstimate>>,
+    pub privacy_config: Box<Account<'info, PrivacyConfig>>,
+    #[account(mut)]
+    pub campaign: Box<Account<'info, Campaign>>,
+}
+
+#[derive(Accounts)]
+pub struct InitVaultStats<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        has_one = admin @ IapError::Unauthorized
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = VaultStats::SPACE,
+        seeds = [VAULT_STATS_SEED],
+        bump
+    )]
+    pub vault_stats: Box<Account<'info, VaultStats>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("aggregate_tier_histogram", admin)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct AggregateTierHistogram<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        has_one = admin @ IapError::Unauthorized,
+        constraint = !vault_config.pause.query @ IapError::QueryPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(mut, seeds = [VAULT_STATS_SEED], bump = vault_stats.bump)]
+    pub vault_stats: Box<Account<'info, VaultStats>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = admin,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_AGGREGATE_TIER_HISTOGRAM))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("aggregate_tier_histogram")]
+#[derive(Accounts)]
+pub struct AggregateTierHistogramCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_AGGREGATE_TIER_HISTOGRAM))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as a writable CallbackAccount by aggregate_tier_histogram
+    #[account(mut)]
+    pub vault_stats: Box<Account<'info, VaultStats>>,
+}
+
+#[queue_computation_accounts("reveal_tier_histogram", admin)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct RevealTierHistogram<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        has_one = admin @ IapError::Unauthorized,
+        constraint = !vault_config.pause.query @ IapError::QueryPaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(seeds = [PRIVACY_CONFIG_SEED], bump = privacy_config.bump)]
+    pub privacy_config: Box<Account<'info, PrivacyConfig>>,
+
+    #[account(mut, seeds = [VAULT_STATS_SEED], bump = vault_stats.bump)]
+    pub vault_stats: Box<Account<'info, VaultStats>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = admin,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_TIER_HISTOGRAM))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("reveal_tier_histogram")]
+#[derive(Accounts)]
+pub struct RevealTierHistogramCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_REVEAL_TIER_HISTOGRAM))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
+
+    // Passed as CallbackAccounts by reveal_tier_histogram
+    #[account(mut)]
+    pub vault_stats: Box<Account<'info, VaultStats>>,
+    pub privacy_config: Box<Account<'info, PrivacyConfig>>,
+}
+
+#[derive(Accounts)]
+pub struct ResetVaultStats<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        has_one = admin @ IapError::Unauthorized
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(mut, seeds = [VAULT_STATS_SEED], bump = vault_stats.bump)]
+    pub vault_stats: Box<Account<'info, VaultStats>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseAudienceEstimate<'info> {
+    #[account(mut)]
+    pub advertiser: Signer<'info>,
+
+    #[account(
+        mut,
+        close = advertiser,
+        seeds = [AUDIENCE_ESTIMATE_SEED, audience_estimate.criteria.as_ref()],
+        bump = audience_estimate.bump,
+        has_one = advertiser @ IapError::Unauthorized
+    )]
+    pub audience_estimate: Box<Account<'info, AudienceEstimate>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseInterestProfile<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [USER_PROFILE_SEED, user.key().as_ref()],
+        bump,
+        constraint = user_profile.owner == user.key() @ IapError::UnauthorizedUpdater
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    // The owner's quorum submissions, if a round was ever opened
+    #[account(
+        mut,
+        close = user,
+        seeds = [PENDING_SUBMISSION_SEED, user.key().as_ref()],
+        bump = pending_submission.bump
+    )]
+    pub pending_submission: Option<Box<Account<'info, PendingProfileSubmission>>>,
+}
+
+#[derive(Accounts)]
+pub struct CloseClaimRecord<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [CLAIM_RECORD_SEED, claim_record.campaign.as_ref(), user.key().as_ref()],
+        bump = claim_record.bump
+    )]
+    pub claim_record: Box<Account<'info, ClaimRecord>>,
+
+    #[account(address = claim_record.campaign)]
+    /// CHECK: the record's campaign, possibly already closed; read in close_claim_record
+    pub campaign: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateProfile<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: may still have an older, shorter layout, so it can't be loaded as
+    /// Account<UserProfile> before the realloc. Seeds and owner are checked here,
+    /// the discriminator when deserializing in the handler.
+    #[account(
+        mut,
+        seeds = [USER_PROFILE_SEED, user.key().as_ref()],
+        bump,
+        owner = crate::ID,
+        realloc = UserProfile::SPACE,
+        realloc::payer = user,
+        realloc::zero = true
+    )]
+    pub user_profile: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVault<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + VaultConfig::INIT_SPACE,
+        seeds = [VAULT_CONFIG_SEED],
+        bump
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(seeds = [TIER_POLICY_SEED], bump = tier_policy.bump)]
+    pub tier_policy: Account<'info, TierPolicy>,
+
+    // Only the upgrade authority of this program may initialize the vault
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::IapVault>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ IapError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVaultConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        has_one = admin @ IapError::Unauthorized
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = vault_config.pending_admin == Some(pending_admin.key()) @ IapError::Unauthorized
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+}
+
+#[derive(Accounts)]
+pub struct InitTierPolicy<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TierPolicy::INIT_SPACE,
+        seeds = [TIER_POLICY_SEED],
+        bump
+    )]
+    pub tier_policy: Account<'info, TierPolicy>,
+
+    // Only the upgrade authority of this program may create the policy
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::IapVault>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ IapError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateTierPolicy<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        has_one = admin @ IapError::Unauthorized
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        mut,
+        seeds = [TIER_POLICY_SEED],
+        bump = tier_policy.bump
+    )]
+    pub tier_policy: Account<'info, TierPolicy>,
+}
+
+#[derive(Accounts)]
+pub struct InitPrivacyConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PrivacyConfig::INIT_SPACE,
+        seeds = [PRIVACY_CONFIG_SEED],
+        bump
+    )]
+    pub privacy_config: Account<'info, PrivacyConfig>,
+
+    // Only the upgrade authority of this program may create the config
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::IapVault>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ IapError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePrivacyConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        has_one = admin @ IapError::Unauthorized
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        mut,
+        seeds = [PRIVACY_CONFIG_SEED],
+        bump = privacy_config.bump
+    )]
+    pub privacy_config: Account<'info, PrivacyConfig>,
+}
+
+#[derive(Accounts)]
+pub struct InitOracleRegistry<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + OracleRegistry::INIT_SPACE,
+        seeds = [ORACLE_REGISTRY_SEED],
+        bump
+    )]
+    pub oracle_registry: Account<'info, OracleRegistry>,
+
+    // Only the upgrade authority of this program may create the registry
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::IapVault>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ IapError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateOracleRegistry<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        has_one = admin @ IapError::Unauthorized
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        mut,
+        seeds = [ORACLE_REGISTRY_SEED],
+        bump = oracle_registry.bump
+    )]
+    pub oracle_registry: Account<'info, OracleRegistry>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct SubmitOracleProfile<'info> {
+    pub oracle: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.store @ IapError::StorePaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(seeds = [ORACLE_REGISTRY_SEED], bump = oracle_registry.bump)]
+    pub oracle_registry: Box<Account<'info, OracleRegistry>>,
+
+    #[account(
+        mut,
+        seeds = [PENDING_SUBMISSION_SEED, owner.as_ref()],
+        bump = pending_submission.bump,
+        constraint = pending_submission.round_open @ IapError::QuorumRoundNotOpen
+    )]
+    pub pending_submission: Box<Account<'info, PendingProfileSubmission>>,
+}
+
+#[derive(Accounts)]
+pub struct OpenQuorumRound<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.store @ IapError::StorePaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserProfile::SPACE,
+        seeds = [USER_PROFILE_SEED, user.key().as_ref()],
+        bump
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = PendingProfileSubmission::SPACE,
+        seeds = [PENDING_SUBMISSION_SEED, user.key().as_ref()],
+        bump
+    )]
+    pub pending_submission: Box<Account<'info, PendingProfileSubmission>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[queue_computation_accounts("median_interest_profile", payer)]
+#[derive(Accounts)]
+#[instruction(computation_offset: u64)]
+pub struct FinalizeOracleQuorum<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [VAULT_CONFIG_SEED],
+        bump = vault_config.bump,
+        constraint = !vault_config.pause.store @ IapError::StorePaused
+    )]
+    pub vault_config: Box<Account<'info, VaultConfig>>,
+
+    #[account(
+        mut,
+        seeds = [PENDING_SUBMISSION_SEED, pending_submission.owner.as_ref()],
+        bump = pending_submission.bump
+    )]
+    pub pending_submission: Box<Account<'info, PendingProfileSubmission>>,
+
+    // Created by the owner in open_quorum_round, never here
+    #[account(
+        mut,
+        seeds = [USER_PROFILE_SEED, pending_submission.owner.as_ref()],
+        bump
+    )]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+
+    #[account(seeds = [ORACLE_REGISTRY_SEED], bump = oracle_registry.bump)]
+    pub oracle_registry: Box<Account<'info, OracleRegistry>>,
+
+    #[account(seeds = [TIER_POLICY_SEED], bump = tier_policy.bump)]
+    pub tier_policy: Box<Account<'info, TierPolicy>>,
+
+    #[account(
+        init_if_needed,
+        space = 9,
+        payer = payer,
+        seeds = [&SIGN_PDA_SEED],
+        bump,
+        address = derive_sign_pda!(),
+    )]
+    pub sign_pda_account: Account<'info, ArciumSignerAccount>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut, address = derive_mempool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: mempool_account, checked by the arcium program.
+    pub mempool_account: UncheckedAccount<'info>,
+    #[account(mut, address = derive_execpool_pda!(mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: executing_pool, checked by the arcium program.
+    pub executing_pool: UncheckedAccount<'info>,
+    #[account(mut, address = derive_comp_pda!(computation_offset, mxe_account, IapError::ClusterNotSet))]
+    /// CHECK: computation_account, checked by the arcium program.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MEDIAN_INTEREST_PROFILE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(mut, address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(mut, address = ARCIUM_FEE_POOL_ACCOUNT_ADDRESS)]
+    pub pool_account: Box<Account<'info, FeePool>>,
+    #[account(mut, address = ARCIUM_CLOCK_ACCOUNT_ADDRESS)]
+    pub clock_account: Box<Account<'info, ClockAccount>>,
+    pub system_program: Program<'info, System>,
+    pub arcium_program: Program<'info, Arcium>,
+}
+
+#[callback_accounts("median_interest_profile")]
+#[derive(Accounts)]
+pub struct MedianInterestProfileCallback<'info> {
+    pub arcium_program: Program<'info, Arcium>,
+    #[account(address = derive_comp_def_pda!(COMP_DEF_OFFSET_MEDIAN_INTEREST_PROFILE))]
+    pub comp_def_account: Box<Account<'info, ComputationDefinitionAccount>>,
+    #[account(address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    /// CHECK: computation_account, checked by arcium program via constraints in the callback context.
+    pub computation_account: UncheckedAccount<'info>,
+    #[account(address = derive_cluster_pda!(mxe_account, IapError::ClusterNotSet))]
+    pub cluster_account: Box<Account<'info, Cluster>>,
+    #[account(address = ::anchor_lang::solana_program::sysvar::instructions::ID)]
+    /// CHECK: instructions_sysvar, checked by the account constraint
+    pub instructions_sysvar: AccountInfo<'info>,
 
-#[program]
-pub mod iap_vault {
-    use super::*;
+    // Passed as writable CallbackAccounts by finalize_oracle_quorum
+    #[account(mut)]
+    pub user_profile: Box<Account<'info, UserProfile>>,
+    #[account(mut)]
+    pub pending_submission: Box<Account<'info, PendingProfileSubmission>>,
+}
 
-    // Store the full Interest Profile (6 fields) encrypted in Arcium MXE
-    pub fn store_interest_profile(
-        ctx: Context<StoreInterestProfile>, 
-        encrypted_profile: SharedEncryptedStruct<6> // Input: 6 fields encrypted by the user (Helius script)
-    ) -> Result<()> {
-        
-        // 1. Reconstruct Enc<Shared, InterestProfileTuple> manually from the input struct
-        // We need to convert bytes back to ArcisFields for the EncData
-        let mut enc_data_values = Vec::new();
-        for cipher_bytes in encrypted_profile.ciphertexts.iter() {
-           enc_data_values.push(EvalValue::Base(bytes_to_field(cipher_bytes)));
-        }
-        
-        // EncData::from_values for tuple type (u8, u32, u64, u64, u32, u32)
-        // The tuple will be serialized as 6 separate fields
-        let enc_data = EncData::<InterestProfileTuple>::from_values(&enc_data_values);
-
-        // Reconstruct pubkey
-        let pubkey = ArcisX25519Pubkey::from_uint8(&encrypted_profile.encryption_key);
-
-        // 2. Decrypt (Convert to Arcis/Plaintext inside circuit)
-        // This decrypts the Shared encryption to get the InterestProfileTuple
-        let profile: InterestProfileTuple = enc_data.to_arcis_with_pubkey_and_nonce(pubkey, encrypted_profile.nonce);
-
-        // 3. Encrypt for MXE (Vault)
-        // This generates a new random nonce for the MXE
-        let mxe_enc: Enc<Mxe, InterestProfileTuple> = Mxe::get().from_arcis(profile);
-
-        // 4. Serialize Enc<Mxe, InterestProfileTuple> manually to MXEEncryptedStruct (via fields / handle_outputs)
-        // structure: [Nonce (1 field), Ciphertext (6 fields for the tuple)]
-        let mut outputs = Vec::new();
-        mxe_enc.handle_outputs(&mut outputs);
-
-        // Check format: Mxe owner uses 1 field (Nonce). EncData<InterestProfileTuple> uses 6 fields (Ciphertext).
-        // Total should be 7 fields (1 nonce + 6 ciphertexts).
-        if outputs.len() < 7 {
-            return Err(ProgramError::InvalidAccountData.into()); 
-        }
-
-        // Extract Nonce
-        let nonce_field = match outputs[0] {
-            EvalValue::Base(f) => f,
-            _ => return Err(ProgramError::InvalidAccountData.into()),
-        };
-        // Convert nonce field to u128. 
-        // to_le_bytes returns 32 bytes (256 bits). We take 16.
-        let nonce_bytes = nonce_field.to_le_bytes(); 
-        let mut nonce_arr = [0u8; 16];
-        nonce_arr.copy_from_slice(&nonce_bytes[0..16]);
-        let nonce = u128::from_le_bytes(nonce_arr);
-
-        // Extract Ciphertexts (6 fields for the tuple)
-        let mut ciphertexts = [[0u8; 32]; 6];
-        for (i, output) in outputs.iter().skip(1).enumerate() {
-            if i >= 6 { break; }
-             match output {
-                EvalValue::Base(f) => {
-                    let b = f.to_le_bytes();
-                    // Copy into 32 byte array (pad or trim if needed, usually 32 bytes)
-                    let mut arr = [0u8; 32];
-                    arr.copy_from_slice(&b[0..32]); // Ensure size match
-                    ciphertexts[i] = arr;
-                },
-                _ => return Err(ProgramError::InvalidAccountData.into()),
-            }
-        }
+#[derive(Accounts)]
+pub struct FlagStaleProfile<'info> {
+    #[account(mut)]
+    pub user_profile: Box<Account<'info, UserProfile>>,
 
-        // 5. Store result
-        ctx.accounts.state.interest_profile = MXEWrapper {
-            inner: MXEEncryptedStruct {
-                nonce,
-                ciphertexts,
-            }
-        };
-        
-        Ok(())
-    }
+    #[account(seeds = [ORACLE_REGISTRY_SEED], bump = oracle_registry.bump)]
+    pub oracle_registry: Box<Account<'info, OracleRegistry>>,
+}
 
-    // Legacy function for backward compatibility (deprecated)
-    #[deprecated(note = "Use store_interest_profile instead")]
-    pub fn store_tier(
-        ctx: Context<StoreTier>, 
-        encrypted_tier: SharedEncryptedStruct<1>
-    ) -> Result<()> {
-        // Keep old implementation for compatibility
-        // ... (keeping the old code for now)
-        Ok(())
-    }
+#[init_computation_definition_accounts("store_interest_profile", payer)]
+#[derive(Accounts)]
+pub struct InitStoreInterestProfileCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
 }
 
-// 4. THE STORAGE STRUCTURE
-#[account]
-pub struct UserProfile {
-    // We use a wrapper because MXEEncryptedStruct does not implement Clone, which #[account] requires.
-    // The wrapper manually implements Clone by copying the public fields.
-    // Updated to store full InterestProfile (6 fields) instead of just tier
-    pub interest_profile: MXEWrapper<6>, 
+#[init_computation_definition_accounts("check_eligibility", payer)]
+#[derive(Accounts)]
+pub struct InitCheckEligibilityCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
 }
 
-// 5. THE CONTEXT
+#[init_computation_definition_accounts("store_campaign_criteria", payer)]
 #[derive(Accounts)]
-pub struct StoreInterestProfile<'info> {
-    #[account(
-        init_if_needed, 
-        payer = user, 
-        // Space calculation: 8 (discriminator) + 16 (nonce) + (32 * 6) (6 ciphertext fields) = 216 bytes
-        space = 8 + 16 + (32 * 6),
-        seeds = [b"user_profile", user.key().as_ref()], 
-        bump
-    )]
-    pub state: Account<'info, UserProfile>,
+pub struct InitStoreCampaignCriteriaCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
 
+#[init_computation_definition_accounts("match_campaign_criteria", payer)]
+#[derive(Accounts)]
+pub struct InitMatchCampaignCriteriaCompDef<'info> {
     #[account(mut)]
-    pub user: Signer<'info>, 
-    
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
     pub system_program: Program<'info, System>,
 }
 
-// Legacy context for backward compatibility
+#[init_computation_definition_accounts("claim_campaign_reward", payer)]
 #[derive(Accounts)]
-pub struct StoreTier<'info> {
-    #[account(
-        init_if_needed, 
-        payer = user, 
-        space = 8 + 32 + 32 + 16, // Adjust space for wrapper struct overhead (nonce + ciphertexts)
-        seeds = [b"user_profile", user.key().as_ref()], 
-        bump
-    )]
-    pub state: Account<'info, UserProfile>,
+pub struct InitClaimCampaignRewardCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("estimate_audience", payer)]
+#[derive(Accounts)]
+pub struct InitEstimateAudienceCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("reveal_audience_estimate", payer)]
+#[derive(Accounts)]
+pub struct InitRevealAudienceEstimateCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("aggregate_tier_histogram", payer)]
+#[derive(Accounts)]
+pub struct InitAggregateTierHistogramCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("reveal_tier_histogram", payer)]
+#[derive(Accounts)]
+pub struct InitRevealTierHistogramCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("disclose_fields", payer)]
+#[derive(Accounts)]
+pub struct InitDiscloseFieldsCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("median_interest_profile", payer)]
+#[derive(Accounts)]
+pub struct InitMedianInterestProfileCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
+
+#[init_computation_definition_accounts("read_my_profile", payer)]
+#[derive(Accounts)]
+pub struct InitReadMyProfileCompDef<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
+    pub system_program: Program<'info, System>,
+}
 
+#[init_computation_definition_accounts("compute_tier", payer)]
+#[derive(Accounts)]
+pub struct InitComputeTierCompDef<'info> {
     #[account(mut)]
-    pub user: Signer<'info>, 
-    
+    pub payer: Signer<'info>,
+    #[account(mut, address = derive_mxe_pda!())]
+    pub mxe_account: Box<Account<'info, MXEAccount>>,
+    #[account(mut)]
+    /// CHECK: comp_def_account, checked by arcium program.
+    /// Can't check it here as it's not initialized yet.
+    pub comp_def_account: UncheckedAccount<'info>,
+    pub arcium_program: Program<'info, Arcium>,
     pub system_program: Program<'info, System>,
 }
 
+// Legacy context for backward compatibility.
+// It used to init_if_needed the user_profile PDA with only 88 bytes, which left an
+// account too small for a full profile; it no longer creates or touches any account.
+#[derive(Accounts)]
+pub struct StoreTier<'info> {
+    pub user: Signer<'info>,
+}
+
+// Profile lifecycle events. They only ever carry public metadata, never
+// ciphertexts or plaintext profile values.
+#[event]
+pub struct ProfileStored {
+    pub owner: Pubkey,
+    pub slot: u64,
+    pub schema_version: u8,
+    pub computation_offset: u64,
+    pub tier_policy_version: u32,
+    pub attested_by: Pubkey,
+    pub snapshot_slot: u64,
+}
+
+#[event]
+pub struct ProfileUpdated {
+    pub owner: Pubkey,
+    pub slot: u64,
+    pub schema_version: u8,
+    pub computation_offset: u64,
+    pub tier_policy_version: u32,
+    pub attested_by: Pubkey,
+    pub snapshot_slot: u64,
+}
+
+#[event]
+pub struct ProfileClosed {
+    pub owner: Pubkey,
+    pub slot: u64,
+    pub schema_version: u8,
+}
+
+#[event]
+pub struct ClaimRecordClosed {
+    pub campaign: Pubkey,
+    pub claimant: Pubkey,
+    pub status: ClaimStatus,
+    pub slot: u64,
+}
+
+#[event]
+pub struct ProfileMigrated {
+    pub owner: Pubkey,
+    pub slot: u64,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[event]
+pub struct ProfileFlaggedStale {
+    pub owner: Pubkey,
+    pub oracle_id: u32,
+    pub attested_by: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct TierComputed {
+    pub owner: Pubkey,
+    pub slot: u64,
+    pub schema_version: u8,
+    pub computation_offset: u64,
+    pub tier_policy_version: u32,
+}
+
+#[event]
+pub struct ComputationFailed {
+    pub owner: Pubkey,
+    pub slot: u64,
+    pub computation_offset: u64,
+    pub circuit: String,
+}
+
+#[event]
+pub struct EligibilityConsentGranted {
+    pub owner: Pubkey,
+    pub requester: Pubkey,
+    pub max_queries: u32,
+    pub expires_at_slot: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct EligibilityConsentRevoked {
+    pub owner: Pubkey,
+    pub requester: Pubkey,
+    pub slot: u64,
+}
+
+// Result of check_eligibility: the only thing revealed about the profile.
+// computation_account identifies the request (derived from the computation offset).
+#[event]
+pub struct EligibilityChecked {
+    pub owner: Pubkey,
+    pub requester: Pubkey,
+    pub computation_account: Pubkey,
+    pub eligible: bool,
+    pub slot: u64,
+}
+
+// The stored profile as Enc<Shared, InterestProfile> for the owner's encryption_key,
+// in InterestProfileTuple order
+#[event]
+pub struct ProfileReadBack {
+    pub owner: Pubkey,
+    pub encryption_key: [u8; 32],
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; PROFILE_FIELD_COUNT],
+    pub tier_policy_version: u32,
+    pub updated_at: u64,
+    pub computation_account: Pubkey,
+    pub slot: u64,
+}
+
+// Enc<Shared, InterestProfile> for recipient_pubkey, in InterestProfileTuple order.
+// Fields outside field_mask decrypt to 0.
+#[event]
+pub struct ProfileFieldsDisclosed {
+    pub owner: Pubkey,
+    pub recipient_pubkey: [u8; 32],
+    pub field_mask: u8,
+    pub nonce: u128,
+    pub ciphertexts: [[u8; 32]; PROFILE_FIELD_COUNT],
+    pub computation_account: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct CampaignCriteriaStored {
+    pub advertiser: Pubkey,
+    pub criteria: Pubkey,
+    pub criteria_id: u64,
+    pub slot: u64,
+}
+
+// Result of match_campaign_criteria; neither side's values are revealed
+#[event]
+pub struct CampaignCriteriaMatched {
+    pub owner: Pubkey,
+    pub criteria: Pubkey,
+    pub computation_account: Pubkey,
+    pub matched: bool,
+    pub slot: u64,
+}
+
+#[event]
+pub struct CampaignCreated {
+    pub campaign: Pubkey,
+    pub advertiser: Pubkey,
+    pub criteria: Pubkey,
+    pub reward_mint: Option<Pubkey>,
+    pub reward_per_match: u64,
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub max_participants: u32,
+    pub escrow_balance: u64,
+}
+
+#[event]
+pub struct CampaignFunded {
+    pub campaign: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub escrow_balance: u64,
+}
+
+#[event]
+pub struct CampaignPauseChanged {
+    pub campaign: Pubkey,
+    pub paused: bool,
+    pub slot: u64,
+}
+
+#[event]
+pub struct CampaignClosed {
+    pub campaign: Pubkey,
+    pub advertiser: Pubkey,
+    pub refunded_balance: u64,
+    pub participants: u32,
+}
+
+#[event]
+pub struct RewardClaimRequested {
+    pub campaign: Pubkey,
+    pub claimant: Pubkey,
+    pub computation_offset: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct RewardClaimSettled {
+    pub campaign: Pubkey,
+    pub claimant: Pubkey,
+    pub status: ClaimStatus,
+    pub reward_paid: u64,
+    // Withheld by a Token-2022 transfer-fee mint, out of reward_paid
+    pub transfer_fee: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct AudienceBatchCounted {
+    pub criteria: Pubkey,
+    pub profiles_scanned: u32,
+    pub slot: u64,
+}
+
+// audience_size is noisy (epsilon_millis / 1000 differential privacy), and 0
+// whenever meets_floor is false
+#[event]
+pub struct AudienceEstimated {
+    pub criteria: Pubkey,
+    pub advertiser: Pubkey,
+    pub profiles_scanned: u32,
+    pub k_anonymity_floor: u32,
+    pub epsilon_millis: u32,
+    pub meets_floor: bool,
+    pub audience_size: u32,
+    pub slot: u64,
+}
+
+#[event]
+pub struct TierHistogramAggregated {
+    pub epoch: u32,
+    pub profiles_scanned: u32,
+    pub slot: u64,
+}
+
+// Buckets are noisy, and reported as 0 when below k_anonymity_floor
+#[event]
+pub struct TierHistogramRevealed {
+    pub epoch: u32,
+    pub profiles_scanned: u32,
+    pub k_anonymity_floor: u32,
+    pub epsilon_millis: u32,
+    pub privacy_spent_millis: u32,
+    pub bronze: u32,
+    pub silver: u32,
+    pub gold: u32,
+    pub platinum: u32,
+    pub slot: u64,
+}
+
+// Admin lifecycle. AdminChanged is emitted when the vault is initialized
+// (previous_admin = default) and whenever a transfer is accepted.
+#[event]
+pub struct AdminChanged {
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct AdminTransferProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct AdminTransferCancelled {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct VaultPauseChanged {
+    pub admin: Pubkey,
+    pub store: bool,
+    pub query: bool,
+    pub campaigns: bool,
+    pub slot: u64,
+}
+
+#[event]
+pub struct FeeSettingsUpdated {
+    pub admin: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub store_fee_lamports: u64,
+    pub query_fee_lamports: u64,
+    pub slot: u64,
+}
+
+// Oracle registry changes
+#[event]
+pub struct OracleAdded {
+    pub oracle_id: u32,
+    pub oracle: Pubkey,
+    pub name: String,
+    pub data_source: String,
+    pub min_update_interval_slots: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct QuorumRoundOpened {
+    pub owner: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OracleProfileSubmitted {
+    pub owner: Pubkey,
+    pub oracle_id: u32,
+    pub oracle: Pubkey,
+    pub snapshot_slot: u64,
+    // Submissions in the round so far, out of the registry's quorum_size
+    pub submissions: u8,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OracleQuorumUpdated {
+    pub quorum_threshold: u8,
+    pub quorum_size: u8,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OracleRemoved {
+    pub oracle_id: u32,
+    pub oracle: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct OracleKeyRotated {
+    pub oracle_id: u32,
+    // Differs from oracle_id when the rotation revoked the old attestations
+    pub new_oracle_id: u32,
+    pub old_oracle: Pubkey,
+    pub new_oracle: Pubkey,
+    pub slot: u64,
+}
+
+#[event]
+pub struct DeprecatedInstructionCalled {
+    pub caller: Pubkey,
+    pub instruction: String,
+    pub replacement: String,
+    pub slot: u64,
+}
+
+#[error_code]
+pub enum IapError {
+    #[msg("The computation was aborted")]
+    AbortedComputation,
+    #[msg("Cluster not set")]
+    ClusterNotSet,
+    #[msg("Signer is not allowed to perform this action")]
+    Unauthorized,
+    #[msg("Tier thresholds must not decrease from SILVER to GOLD to PLATINUM")]
+    InvalidTierPolicy,
+    #[msg("store_tier has been retired, use store_interest_profile")]
+    StoreTierDeprecated,
+    #[msg("Expected exactly one ciphertext per InterestProfile field (6)")]
+    CiphertextCountMismatch,
+    #[msg("Ciphertext is not a valid field element encoding")]
+    InvalidFieldEncoding,
+    #[msg("x25519 encryption public key is invalid")]
+    InvalidEncryptionKey,
+    #[msg("Nonce was already used for the previous submission, encrypt with a fresh nonce")]
+    NonceReuse,
+    #[msg("Computation result is for an outdated request on this profile")]
+    StaleProfile,
+    #[msg("Signer is not allowed to update this profile")]
+    UnauthorizedUpdater,
+    #[msg("Profile already uses the current schema version")]
+    ProfileAlreadyMigrated,
+    #[msg("No encrypted profile has been stored yet")]
+    ProfileNotStored,
+    #[msg("Campaign criteria have not been encrypted for the MXE yet")]
+    CriteriaNotReady,
+    #[msg("Campaign reward, participant limit or amount must be greater than zero")]
+    InvalidCampaignParams,
+    #[msg("Campaign must start before it ends, and end in the future")]
+    InvalidCampaignSchedule,
+    #[msg("Campaign is paused")]
+    CampaignPaused,
+    #[msg("Campaign is not paused")]
+    CampaignNotPaused,
+    #[msg("Arithmetic overflow")]
+    MathOverflow,
+    #[msg("Campaign is not running at the current slot")]
+    CampaignNotActive,
+    #[msg("Campaign has reached its maximum number of participants")]
+    CampaignFull,
+    #[msg("Campaign escrow cannot cover another reward")]
+    InsufficientEscrow,
+    #[msg("Reward for this campaign was already claimed")]
+    AlreadyClaimed,
+    #[msg("Campaign still has claims waiting for their eligibility result")]
+    CampaignHasPendingClaims,
+    #[msg("Token campaign claims need the reward mint, vault, token account and token program")]
+    MissingTokenAccounts,
+    #[msg("Account does not match the campaign reward mint")]
+    RewardMintMismatch,
+    #[msg("Escrow vault is not the campaign's associated token account")]
+    InvalidEscrowVault,
+    #[msg("Reward mint uses a Token-2022 extension the escrow cannot pay out")]
+    UnsupportedRewardMint,
+    #[msg("Profile batches take 1 to 4 stored profiles in ascending key order, never seen before")]
+    InvalidProfileBatch,
+    #[msg("Audience estimate already has a computation in flight")]
+    EstimateInProgress,
+    #[msg("Audience estimate was already revealed")]
+    EstimateFinalized,
+    #[msg("Privacy config needs k >= 2, epsilon in [0.25, 10] and budgets of at least one epsilon")]
+    InvalidPrivacyConfig,
+    #[msg("Vault stats already have a computation in flight")]
+    StatsInProgress,
+    #[msg("Privacy budget is exhausted, no further aggregate can be revealed")]
+    PrivacyBudgetExhausted,
+    #[msg("Field mask must select at least one of the 6 profile fields and nothing else")]
+    InvalidFieldMask,
+    #[msg("Oracle key is the default key or already registered")]
+    InvalidOracle,
+    #[msg("Oracle registry is full")]
+    OracleRegistryFull,
+    #[msg("Oracle name and data source must be 1 to 32 bytes")]
+    InvalidOracleMetadata,
+    #[msg("Profile submissions must be preceded by an ed25519 oracle attestation instruction")]
+    MissingAttestation,
+    #[msg("Oracle attestation does not sign this profile submission")]
+    InvalidAttestation,
+    #[msg("Attestation is not signed by a registered profiler oracle")]
+    UnknownOracle,
+    #[msg("Attested snapshot is in the future, too old, or not newer than the stored one")]
+    StaleAttestation,
+    #[msg("Snapshot is closer to the stored one than the oracle's update interval")]
+    UpdateTooFrequent,
+    #[msg("Profile was attested by a removed oracle, store a freshly attested profile")]
+    ProfileStale,
+    #[msg("Profile's attesting oracle is still registered, or it is already flagged")]
+    ProfileNotStale,
+    #[msg("Oracle quorum already has a computation in flight")]
+    QuorumInProgress,
+    #[msg("Oracle quorum already has all its submissions")]
+    QuorumComplete,
+    #[msg("Oracle quorum does not have enough submissions yet")]
+    QuorumIncomplete,
+    #[msg("Oracle already submitted a profile in this quorum round")]
+    DuplicateOracleSubmission,
+    #[msg("Storing profiles is paused")]
+    StorePaused,
+    #[msg("Queries on stored profiles are paused")]
+    QueryPaused,
+    #[msg("Campaigns are paused")]
+    CampaignsPaused,
+    #[msg("Fee recipient does not match the vault config")]
+    InvalidFeeRecipient,
+    #[msg("New admin must differ from the current admin and the default key")]
+    InvalidAdmin,
+    #[msg("No admin transfer is pending")]
+    NoPendingAdmin,
+    #[msg("Computation result is for an outdated request on this account")]
+    StaleComputation,
+    #[msg("Campaign criteria are already stored or have a computation in flight")]
+    CriteriaAlreadyStored,
+    #[msg("Advertiser has stored the maximum number of campaign criteria")]
+    CriteriaLimitReached,
+    #[msg("Consent needs at least one query and an expiry slot in the future")]
+    InvalidConsent,
+    #[msg("Eligibility consent has expired or has no queries left")]
+    ConsentExhausted,
+    #[msg("Profile has no oracle attestation, store a freshly attested profile")]
+    ProfileNotAttested,
+    #[msg("Owner has no open quorum round, it must call open_quorum_round first")]
+    QuorumRoundNotOpen,
+    #[msg("Claim is still pending, or its campaign is still running")]
+    ClaimRecordInUse,
+    #[msg("Eligibility consent does not belong to this profile")]
+    ConsentMismatch,
+    #[msg("Claim is not pending, or its computation may still complete")]
+    ClaimNotExpired,    #[msg("Campaign does not target the estimated criteria")]
+    CriteriaMismatch,
+    #[msg("Quorum threshold must be between 1 and the quorum size, at most 5")]
+    InvalidQuorumConfig,
+}
+
 // Wrapper for MXEEncryptedStruct to implement Clone
 pub struct MXEWrapper<const LEN: usize> {
     pub inner: MXEEncryptedStruct<LEN>,
@@ -179,17 +5772,458 @@ impl<const LEN: usize> AnchorDeserialize for MXEWrapper<LEN> {
     }
 }
 
-// Helper: Convert [u8; 32] to ArcisCiphertext (BaseField)
-// Logic adapted from ArcisX25519Pubkey::from_uint8 source
-fn bytes_to_field(s: &[u8; 32]) -> ArcisCiphertext {
-     // Use existing From<u64> impl on BaseField
-     // ArcisCiphertext::from(u64) is standard
-     let two_power_eight = ArcisCiphertext::from(256);
-     let mut x = ArcisCiphertext::from(0);
-     let mut factor = ArcisCiphertext::from(1);
-     for &b in s {
-         x = x + ArcisCiphertext::from(b as u64) * factor; // ArcisCiphertext arithmetic
-         factor = factor * two_power_eight;
-     }
-     x
-}
\ No newline at end of file
+// Helper: push one encrypted argument per InterestProfileTuple field, using the
+// integer width of each field so the circuit decodes them as (u8, u32, u64, u64, u32, u32)
+fn push_profile_ciphertexts(
+    args: ArgBuilder,
+    ciphertexts: &[[u8; 32]; PROFILE_FIELD_COUNT],
+) -> ArgBuilder {
+    args.encrypted_u8(ciphertexts[0])
+        .encrypted_u32(ciphertexts[1])
+        .encrypted_u64(ciphertexts[2])
+        .encrypted_u64(ciphertexts[3])
+        .encrypted_u32(ciphertexts[4])
+        .encrypted_u32(ciphertexts[5])
+}
+
+// Helper: push the TierPolicy as plaintext circuit inputs, in circuit parameter order:
+// policy_version, silver, gold, platinum (each TierThresholds field by field)
+fn push_tier_policy(args: ArgBuilder, policy: &TierPolicy) -> ArgBuilder {
+    let mut args = args.plaintext_u32(policy.version);
+    for thresholds in [&policy.silver, &policy.gold, &policy.platinum] {
+        args = args
+            .plaintext_u32(thresholds.nft_count_above)
+            .plaintext_u64(thresholds.sol_lamports_above)
+            .plaintext_u64(thresholds.min_trading_volume_cents)
+            .plaintext_u32(thresholds.min_defi_interactions);
+    }
+    args
+}
+
+// Helper: push EligibilityCriteria as plaintext circuit inputs, field by field
+fn push_eligibility_criteria(args: ArgBuilder, criteria: &EligibilityCriteria) -> ArgBuilder {
+    args.plaintext_u8(criteria.min_tier)
+        .plaintext_u32(criteria.min_nft_count)
+        .plaintext_u64(criteria.min_sol_lamports)
+        .plaintext_u64(criteria.min_trading_volume_cents)
+        .plaintext_u32(criteria.min_token_holdings)
+        .plaintext_u32(criteria.min_defi_interactions)
+}
+
+// Higher tiers must be at least as hard to reach as lower ones
+fn validate_tier_thresholds(
+    silver: &TierThresholds,
+    gold: &TierThresholds,
+    platinum: &TierThresholds,
+) -> Result<()> {
+    let ordered = |lower: &TierThresholds, higher: &TierThresholds| {
+        lower.nft_count_above <= higher.nft_count_above
+            && lower.sol_lamports_above <= higher.sol_lamports_above
+            && lower.min_trading_volume_cents <= higher.min_trading_volume_cents
+            && lower.min_defi_interactions <= higher.min_defi_interactions
+    };
+    require!(
+        ordered(silver, gold) && ordered(gold, platinum),
+        IapError::InvalidTierPolicy
+    );
+    Ok(())
+}
+
+// Move lamports from `funder` into the campaign PDA and track them as escrow
+fn fund_campaign_escrow<'info>(
+    funder: &Signer<'info>,
+    campaign: &mut Account<'info, Campaign>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    system_program::transfer(
+        CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: funder.to_account_info(),
+                to: campaign.to_account_info(),
+            },
+        ),
+        amount,
+    )?;
+    campaign.escrow_balance = campaign
+        .escrow_balance
+        .checked_add(amount)
+        .ok_or(IapError::MathOverflow)?;
+    Ok(())
+}
+
+// Pay a vault fee from `payer` to the fee recipient; nothing to do for a zero fee
+fn collect_fee<'info>(
+    payer: &Signer<'info>,
+    fee_recipient: &UncheckedAccount<'info>,
+    system_program: &Program<'info, System>,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Ok(());
+    }
+    system_program::transfer(
+        CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: fee_recipient.to_account_info(),
+            },
+        ),
+        amount,
+    )
+}
+
+// Move reward tokens from the funder into the campaign vault. With a transfer-fee
+// mint the vault receives less than `amount`, so only what actually arrived is
+// added to the escrow balance (and returned).
+fn fund_campaign_token_escrow<'info>(
+    funder: &Signer<'info>,
+    funder_token_account: &InterfaceAccount<'info, TokenAccount>,
+    escrow_vault: &mut InterfaceAccount<'info, TokenAccount>,
+    reward_mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    campaign: &mut Account<'info, Campaign>,
+    amount: u64,
+) -> Result<u64> {
+    let balance_before = escrow_vault.amount;
+    token_interface::transfer_checked(
+        CpiContext::new(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: funder_token_account.to_account_info(),
+                mint: reward_mint.to_account_info(),
+                to: escrow_vault.to_account_info(),
+                authority: funder.to_account_info(),
+            },
+        ),
+        amount,
+        reward_mint.decimals,
+    )?;
+    escrow_vault.reload()?;
+
+    let received = escrow_vault
+        .amount
+        .checked_sub(balance_before)
+        .ok_or(IapError::MathOverflow)?;
+    campaign.escrow_balance = campaign
+        .escrow_balance
+        .checked_add(received)
+        .ok_or(IapError::MathOverflow)?;
+    Ok(received)
+}
+
+// Whether a token payout from `escrow_vault` to `recipient` can go through. Either
+// side may have been closed, frozen or reopened for another mint since the claim was
+// queued.
+fn can_transfer_tokens(
+    escrow_vault: &AccountInfo,
+    recipient: &AccountInfo,
+    mint: Pubkey,
+    token_program: &AccountInfo,
+) -> bool {
+    [escrow_vault, recipient].into_iter().all(|info| {
+        info.owner == token_program.key
+            && info.try_borrow_data().is_ok_and(|data| {
+                TokenAccount::try_deserialize(&mut &data[..])
+                    .is_ok_and(|account| account.mint == mint && !account.is_frozen())
+            })
+    })
+}
+
+// Pay `amount` of the reward mint out of the campaign vault, signed by the campaign
+// PDA. Transfer-fee mints go through transfer_checked_with_fee so the fee is
+// asserted rather than silently taken. Returns the withheld fee.
+fn pay_from_token_escrow<'info>(
+    campaign: &Account<'info, Campaign>,
+    reward_mint: &AccountInfo<'info>,
+    escrow_vault: &AccountInfo<'info>,
+    recipient: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<u64> {
+    let decimals = InterfaceAccount::<Mint>::try_from(reward_mint)?.decimals;
+    let fee = transfer_fee_for(reward_mint, amount)?;
+
+    let campaign_id = campaign.campaign_id.to_le_bytes();
+    let bump = [campaign.bump];
+    let seeds: &[&[u8]] = &[CAMPAIGN_SEED, campaign.advertiser.as_ref(), &campaign_id, &bump];
+    let signer_seeds = &[seeds];
+
+    if fee > 0 {
+        transfer_checked_with_fee(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                TransferCheckedWithFee {
+                    token_program_id: token_program.clone(),
+                    source: escrow_vault.clone(),
+                    mint: reward_mint.clone(),
+                    destination: recipient.clone(),
+                    authority: campaign.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            decimals,
+            fee,
+        )?;
+    } else {
+        token_interface::transfer_checked(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                TransferChecked {
+                    from: escrow_vault.clone(),
+                    mint: reward_mint.clone(),
+                    to: recipient.clone(),
+                    authority: campaign.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+            decimals,
+        )?;
+    }
+    Ok(fee)
+}
+
+fn has_transfer_fee(mint: &AccountInfo) -> Result<bool> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(false);
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(state.get_extension::<TransferFeeConfig>().is_ok())
+}
+
+// Fee a Token-2022 transfer-fee mint withholds on `amount` in the current epoch,
+// 0 for SPL Token mints and mints without the extension
+fn transfer_fee_for(mint: &AccountInfo, amount: u64) -> Result<u64> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(0);
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    match state.get_extension::<TransferFeeConfig>() {
+        Ok(config) => config
+            .calculate_epoch_fee(Clock::get()?.epoch, amount)
+            .ok_or_else(|| error!(IapError::MathOverflow)),
+        Err(_) => Ok(0),
+    }
+}
+
+// Token-2022 mints the escrow cannot pay out from: transfer hooks need extra
+// accounts the claim callback does not carry, and non-transferable tokens never move
+fn validate_reward_mint(mint: &AccountInfo) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let unsupported = state.get_extension_types()?.into_iter().any(|extension| {
+        matches!(
+            extension,
+            ExtensionType::TransferHook | ExtensionType::NonTransferable
+        )
+    });
+    require!(!unsupported, IapError::UnsupportedRewardMint);
+    Ok(())
+}
+
+// exp(-epsilon_millis / 1000) as a probability scaled by 2^32, the continue threshold
+// of the geometric noise samples. Computed as exp(-0.001)^epsilon_millis by
+// square-and-multiply, since there is no floating point exp on-chain.
+fn noise_threshold(epsilon_millis: u32) -> u64 {
+    let mut threshold: u128 = 1 << 32;
+    let mut base = EXP_NEG_MILLI_Q32;
+    let mut exponent = epsilon_millis;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            threshold = (threshold * base) >> 32;
+        }
+        base = (base * base) >> 32;
+        exponent >>= 1;
+    }
+    threshold as u64
+}
+
+// Charges one reveal of `epsilon_millis` to a budget, failing once it would exceed
+// `budget_millis`
+fn spend_privacy_budget(spent_millis: &mut u32, budget_millis: u32, epsilon_millis: u32) -> Result<()> {
+    let spent = spent_millis
+        .checked_add(epsilon_millis)
+        .ok_or(IapError::MathOverflow)?;
+    require!(spent <= budget_millis, IapError::PrivacyBudgetExhausted);
+    *spent_millis = spent;
+    Ok(())
+}
+
+// Checks a batch of UserProfile accounts for the batch circuits (estimate_audience,
+// aggregate_tier_histogram): 1 to PROFILE_BATCH_SIZE stored profiles in strictly
+// ascending key order, all above `cursor`. Returns each profile's key and the
+// nonce of its Enc<Mxe, InterestProfile>.
+fn collect_profile_batch(profiles: &[AccountInfo], cursor: Pubkey) -> Result<Vec<(Pubkey, u128)>> {
+    require!(
+        !profiles.is_empty() && profiles.len() <= PROFILE_BATCH_SIZE,
+        IapError::InvalidProfileBatch
+    );
+
+    let mut cursor = cursor;
+    let mut batch = Vec::with_capacity(profiles.len());
+    for info in profiles {
+        require!(info.key() > cursor, IapError::InvalidProfileBatch);
+        require_keys_eq!(*info.owner, crate::ID, IapError::InvalidProfileBatch);
+        let profile = UserProfile::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        profile.require_current()?;
+        batch.push((info.key(), profile.interest_profile.inner.nonce));
+        cursor = info.key();
+    }
+    Ok(batch)
+}
+
+// Helper: close the owner's EligibilityConsent accounts passed to
+// close_interest_profile and refund their rent to the owner
+fn close_eligibility_consents<'info>(
+    consents: &[AccountInfo<'info>],
+    owner: &AccountInfo<'info>,
+) -> Result<()> {
+    for info in consents {
+        require_keys_eq!(*info.owner, crate::ID, IapError::ConsentMismatch);
+        let consent = EligibilityConsent::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        require_keys_eq!(consent.owner, owner.key(), IapError::ConsentMismatch);
+        let address = Pubkey::create_program_address(
+            &[
+                ELIGIBILITY_CONSENT_SEED,
+                consent.owner.as_ref(),
+                consent.requester.as_ref(),
+                &[consent.bump],
+            ],
+            &crate::ID,
+        )
+        .map_err(|_| IapError::ConsentMismatch)?;
+        require_keys_eq!(info.key(), address, IapError::ConsentMismatch);
+
+        emit!(EligibilityConsentRevoked {
+            owner: consent.owner,
+            requester: consent.requester,
+            slot: Clock::get()?.slot,
+        });
+        anchor_lang::common::close(info.clone(), owner.clone())?;
+    }
+    Ok(())
+}
+
+// Helper: push the `included` mask and the PROFILE_BATCH_SIZE profile arguments of
+// the batch circuits. Unused slots repeat the first profile; the mask keeps them
+// out of the result.
+fn push_profile_batch(args: ArgBuilder, batch: &[(Pubkey, u128)]) -> ArgBuilder {
+    let mut args = args.plaintext_u8((1u8 << batch.len()) - 1);
+    for slot in 0..PROFILE_BATCH_SIZE {
+        let (key, nonce) = batch.get(slot).unwrap_or(&batch[0]);
+        args = args
+            .plaintext_u128(*nonce)
+            .account(*key, PROFILE_CIPHERTEXTS_OFFSET, PROFILE_CIPHERTEXTS_LEN);
+    }
+    args
+}
+
+// Checks an Enc<Shared, _> submission with the InterestProfileTuple layout
+// (profiles and campaign criteria) before it is queued
+fn validate_shared_input(
+    ciphertexts: Vec<[u8; 32]>,
+    pubkey: &[u8; 32],
+) -> Result<[[u8; 32]; PROFILE_FIELD_COUNT]> {
+    let ciphertexts: [[u8; 32]; PROFILE_FIELD_COUNT] = ciphertexts
+        .try_into()
+        .map_err(|_| IapError::CiphertextCountMismatch)?;
+    require!(
+        ciphertexts.iter().all(is_canonical_field_element),
+        IapError::InvalidFieldEncoding
+    );
+    require!(*pubkey != [0u8; 32], IapError::InvalidEncryptionKey);
+    Ok(ciphertexts)
+}
+
+// The message a profiler oracle signs for a store_interest_profile submission,
+// see PROFILE_ATTESTATION_DOMAIN
+fn profile_attestation_message(
+    owner: &Pubkey,
+    pubkey: &[u8; 32],
+    ciphertexts: &[[u8; 32]; PROFILE_FIELD_COUNT],
+    nonce: u128,
+    snapshot_slot: u64,
+) -> Vec<u8> {
+    let mut input: Vec<&[u8]> = vec![pubkey];
+    input.extend(ciphertexts.iter().map(|c| c.as_slice()));
+    let ciphertext_hash = hashv(&input);
+
+    let mut message = Vec::with_capacity(PROFILE_ATTESTATION_DOMAIN.len() + 32 + 32 + 16 + 8);
+    message.extend_from_slice(PROFILE_ATTESTATION_DOMAIN);
+    message.extend_from_slice(owner.as_ref());
+    message.extend_from_slice(ciphertext_hash.as_ref());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(&snapshot_slot.to_le_bytes());
+    message
+}
+
+// The Ed25519Program verifies its signatures before any other instruction of the
+// transaction runs, so finding it right before the current instruction proves the
+// signature. What is left is checking that it signs `message` with a registered
+// oracle key, and that all of that is inside the ed25519 instruction itself (offsets
+// pointing into other instructions could smuggle in unrelated data).
+// Returns the attesting oracle's registry entry.
+fn verify_oracle_attestation<'a>(
+    instructions_sysvar: &AccountInfo,
+    registry: &'a OracleRegistry,
+    message: &[u8],
+) -> Result<&'a OracleEntry> {
+    let current = load_current_index_checked(instructions_sysvar)?;
+    require!(current > 0, IapError::MissingAttestation);
+    let ix = load_instruction_at_checked(usize::from(current - 1), instructions_sysvar)?;
+    require_keys_eq!(ix.program_id, ed25519_program::ID, IapError::MissingAttestation);
+
+    let data = &ix.data;
+    require!(
+        data.len() >= ED25519_HEADER_LEN + ED25519_OFFSETS_LEN && data[0] == 1,
+        IapError::InvalidAttestation
+    );
+    let offsets: Vec<u16> = data[ED25519_HEADER_LEN..ED25519_HEADER_LEN + ED25519_OFFSETS_LEN]
+        .chunks_exact(2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    // signature, public key and message offsets, each followed by its instruction index
+    // (u16::MAX = this instruction); the message size sits before its index
+    let (signature_ix, pubkey_offset, pubkey_ix) = (offsets[1], offsets[2], offsets[3]);
+    let (message_offset, message_size, message_ix) = (offsets[4], offsets[5], offsets[6]);
+    require!(
+        signature_ix == u16::MAX && pubkey_ix == u16::MAX && message_ix == u16::MAX,
+        IapError::InvalidAttestation
+    );
+
+    let pubkey_start = usize::from(pubkey_offset);
+    let oracle = data
+        .get(pubkey_start..pubkey_start + 32)
+        .ok_or(IapError::InvalidAttestation)?;
+    let message_start = usize::from(message_offset);
+    let signed = data
+        .get(message_start..message_start + usize::from(message_size))
+        .ok_or(IapError::InvalidAttestation)?;
+    require!(signed == message, IapError::InvalidAttestation);
+
+    let oracle = Pubkey::try_from(oracle).map_err(|_| IapError::InvalidAttestation)?;
+    registry
+        .find_by_key(&oracle)
+        .ok_or_else(|| IapError::UnknownOracle.into())
+}
+
+// Ciphertexts are little-endian encodings of elements of the base field of
+// Curve25519, so they must be strictly below p = 2^255 - 19
+fn is_canonical_field_element(bytes: &[u8; 32]) -> bool {
+    if bytes[31] != 0x7f {
+        return bytes[31] < 0x7f;
+    }
+    if bytes[1..31].iter().any(|&b| b != 0xff) {
+        return true;
+    }
+    bytes[0] < 0xed
+}
@@ -1,22 +1,29 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
-import { PublicKey } from "@solana/web3.js";
+import { LAMPORTS_PER_SOL, PublicKey, SystemProgram } from "@solana/web3.js";
+import {
+  createAccount,
+  createAssociatedTokenAccountIdempotent,
+  createCloseAccountInstruction,
+  createInitializeMintInstruction,
+  createInitializeTransferFeeConfigInstruction,
+  ExtensionType,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getMintLen,
+  mintTo,
+  TOKEN_2022_PROGRAM_ID,
+} from "@solana/spl-token";
 import { IapVault } from "../target/types/iap_vault";
 import { randomBytes } from "crypto";
 import {
   awaitComputationFinalization,
   getArciumEnv,
-  getCompDefAccOffset,
-  getArciumAccountBaseSeed,
-  getArciumProgramId,
-  uploadCircuit,
-  buildFinalizeCompDefTx,
   RescueCipher,
   deserializeLE,
   getMXEPublicKey,
   getMXEAccAddress,
   getMempoolAccAddress,
-  getCompDefAccAddress,
   getExecutingPoolAccAddress,
   getComputationAccAddress,
   getClusterAccAddress,
@@ -25,6 +32,30 @@ import {
 import * as fs from "fs";
 import * as os from "os";
 import { expect } from "chai";
+import {
+  getCompDefPda,
+  initAllCompDefs,
+  VaultCircuit,
+} from "../migrations/compDefs";
+import { initTierPolicy } from "../migrations/tierPolicy";
+import {
+  getVaultConfigPda,
+  initializeVault,
+  proposeAdmin,
+} from "../migrations/vaultConfig";
+import {
+  DEFAULT_PRIVACY_PARAMS,
+  getVaultStatsPda,
+  initPrivacyConfig,
+  initVaultStats,
+} from "../migrations/privacyConfig";
+import {
+  addOracle,
+  createProfileAttestationIx,
+  getOracleRegistryPda,
+  initOracleRegistry,
+  profileAttestationMessage,
+} from "../migrations/oracleRegistry";
 
 describe("IapVault", () => {
   // Configure the client to use the local cluster.
@@ -51,20 +82,234 @@ describe("IapVault", () => {
   const arciumEnv = getArciumEnv();
   const clusterAccount = getClusterAccAddress(arciumEnv.arciumClusterOffset);
 
-  it("Is initialized!", async () => {
-    const owner = readKpJson(`${os.homedir()}/.config/solana/id.json`);
+  // Arcium accounts shared by every queue_computation instruction
+  const arciumAccounts = (
+    computationOffset: anchor.BN,
+    circuit: VaultCircuit,
+  ) => ({
+    computationAccount: getComputationAccAddress(
+      arciumEnv.arciumClusterOffset,
+      computationOffset,
+    ),
+    clusterAccount,
+    mxeAccount: getMXEAccAddress(program.programId),
+    mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
+    executingPool: getExecutingPoolAccAddress(arciumEnv.arciumClusterOffset),
+    compDefAccount: getCompDefPda(program.programId, circuit),
+  });
+
+  const owner = readKpJson(`${os.homedir()}/.config/solana/id.json`);
+  const [userProfilePda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("user_profile"), owner.publicKey.toBuffer()],
+    program.programId,
+  );
+  const pda = (...seeds: Buffer[]) =>
+    PublicKey.findProgramAddressSync(seeds, program.programId)[0];
+  const u64Le = (value: number) =>
+    new anchor.BN(value).toArrayLike(Buffer, "le", 8);
+
+  const airdrop = async (to: PublicKey, sol: number) => {
+    const signature = await provider.connection.requestAirdrop(
+      to,
+      sol * LAMPORTS_PER_SOL,
+    );
+    const latest = await provider.connection.getLatestBlockhash();
+    await provider.connection.confirmTransaction(
+      { signature, ...latest },
+      "confirmed",
+    );
+  };
+
+  let mxePublicKey: Uint8Array;
+  const mxeKey = async () => {
+    if (!mxePublicKey) {
+      mxePublicKey = await getMXEPublicKeyWithRetry(
+        provider as anchor.AnchorProvider,
+        program.programId,
+      );
+    }
+    return mxePublicKey;
+  };
+
+  // Encrypts `plaintext` as an Enc<Shared, _> input under a fresh x25519 key
+  const encryptForMxe = async (plaintext: bigint[]) => {
+    const privateKey = x25519.utils.randomSecretKey();
+    const publicKey = x25519.getPublicKey(privateKey);
+    const cipher = new RescueCipher(
+      x25519.getSharedSecret(privateKey, await mxeKey()),
+    );
+    const nonce = randomBytes(16);
+    return {
+      ciphertexts: cipher.encrypt(plaintext, nonce).map((c) => Array.from(c)),
+      publicKey: Array.from(publicKey),
+      nonce: new anchor.BN(deserializeLE(nonce).toString()),
+    };
+  };
+
+  // A fresh x25519 key to receive an MXE re-encryption with
+  const newReader = async () => {
+    const privateKey = x25519.utils.randomSecretKey();
+    return {
+      publicKey: Array.from(x25519.getPublicKey(privateKey)),
+      cipher: new RescueCipher(
+        x25519.getSharedSecret(privateKey, await mxeKey()),
+      ),
+    };
+  };
+  const randomNonce = () =>
+    new anchor.BN(deserializeLE(randomBytes(16)).toString());
+  const nonceBytes = (nonce: anchor.BN) =>
+    Uint8Array.from(nonce.toArray("le", 16));
+
+  const expectError = async (request: Promise<unknown>, code: string) => {
+    try {
+      await request;
+    } catch (err) {
+      expect(err).to.be.instanceOf(anchor.AnchorError);
+      expect((err as anchor.AnchorError).error.errorCode.code).to.equal(code);
+      return;
+    }
+    expect.fail(`expected ${code}`);
+  };
+
+  const finalize = (computationOffset: anchor.BN) =>
+    awaitComputationFinalization(
+      provider as anchor.AnchorProvider,
+      computationOffset,
+      program.programId,
+      "confirmed",
+    );
+
+  // Advertiser shared by the criteria, campaign and audience tests
+  const advertiser = anchor.web3.Keypair.generate();
+  const criteriaId = 1;
+  const criteriaPda = pda(
+    Buffer.from("campaign_criteria"),
+    advertiser.publicKey.toBuffer(),
+    u64Le(criteriaId),
+  );
+  const advertiserStatePda = pda(
+    Buffer.from("advertiser_state"),
+    advertiser.publicKey.toBuffer(),
+  );
+  const campaignPda = (campaignId: number) =>
+    pda(
+      Buffer.from("campaign"),
+      advertiser.publicKey.toBuffer(),
+      u64Le(campaignId),
+    );
 
-    console.log("Initializing add together computation definition");
-    const initATSig = await initAddTogetherCompDef(
+  // Lamport campaign on the shared criteria, open for claims from now on
+  const createCampaign = async (
+    campaignId: number,
+    reward: anchor.BN,
+    maxParticipants: number,
+    initialFunding: anchor.BN,
+  ) => {
+    const slot = await provider.connection.getSlot("confirmed");
+    await program.methods
+      .createCampaign(
+        new anchor.BN(campaignId),
+        reward,
+        new anchor.BN(slot),
+        new anchor.BN(slot + 10_000),
+        maxParticipants,
+        initialFunding,
+      )
+      .accountsPartial({
+        advertiser: advertiser.publicKey,
+        campaign: campaignPda(campaignId),
+        campaignCriteria: criteriaPda,
+      })
+      .signers([advertiser])
+      .rpc({ commitment: "confirmed" });
+    return campaignPda(campaignId);
+  };
+  const closeCampaign = (campaign: PublicKey) =>
+    program.methods
+      .closeCampaign()
+      .accountsPartial({ advertiser: advertiser.publicKey, campaign })
+      .signers([advertiser])
+      .rpc({ commitment: "confirmed" });
+
+  const claimRecordPda = (campaign: PublicKey, claimant: PublicKey) =>
+    pda(Buffer.from("claim"), campaign.toBuffer(), claimant.toBuffer());
+  // Lamport campaigns take no token accounts
+  const noTokenAccounts = {
+    rewardMint: null,
+    escrowVault: null,
+    claimantTokenAccount: null,
+    tokenProgram: null,
+  };
+  const claimReward = (
+    campaign: PublicKey,
+    computationOffset: anchor.BN,
+    tokenAccounts: Record<string, PublicKey | null> = noTokenAccounts,
+  ) =>
+    program.methods
+      .claimCampaignReward(computationOffset)
+      .accountsPartial({
+        user: owner.publicKey,
+        userProfile: userProfilePda,
+        campaign,
+        campaignCriteria: criteriaPda,
+        claimRecord: claimRecordPda(campaign, owner.publicKey),
+        ...tokenAccounts,
+        ...arciumAccounts(computationOffset, "claim_campaign_reward"),
+      })
+      .signers([owner]);
+
+  const vaultStats = getVaultStatsPda(program.programId);
+  const aggregateTierHistogram = (
+    admin: anchor.web3.Keypair,
+    computationOffset: anchor.BN,
+  ) =>
+    program.methods
+      .aggregateTierHistogram(computationOffset)
+      .accountsPartial({
+        admin: admin.publicKey,
+        vaultStats,
+        ...arciumAccounts(computationOffset, "aggregate_tier_histogram"),
+      })
+      .remainingAccounts([
+        { pubkey: userProfilePda, isSigner: false, isWritable: false },
+      ])
+      .signers([admin]);
+  const revealTierHistogram = (computationOffset: anchor.BN) =>
+    program.methods
+      .revealTierHistogram(computationOffset)
+      .accountsPartial({
+        admin: owner.publicKey,
+        vaultStats,
+        ...arciumAccounts(computationOffset, "reveal_tier_histogram"),
+      })
+      .signers([owner]);
+
+  // Wallet whose profile comes from an oracle quorum
+  const quorumUser = anchor.web3.Keypair.generate();
+  const quorumUserProfile = pda(
+    Buffer.from("user_profile"),
+    quorumUser.publicKey.toBuffer(),
+  );
+  const quorumSubmission = pda(
+    Buffer.from("pending_submission"),
+    quorumUser.publicKey.toBuffer(),
+  );
+
+  it("Stores an encrypted interest profile", async () => {
+    console.log("Initializing vault computation definitions");
+    await initAllCompDefs(
+      provider as anchor.AnchorProvider,
       program,
       owner,
-      false,
-      false,
-    );
-    console.log(
-      "Add together computation definition initialized with signature",
-      initATSig,
     );
+    await initTierPolicy(provider as anchor.AnchorProvider, program, owner);
+    await initializeVault(provider as anchor.AnchorProvider, program, owner);
+    // The test wallet doubles as the profiler oracle
+    await initOracleRegistry(provider as anchor.AnchorProvider, program, owner);
+    await addOracle(program, owner, owner.publicKey);
+    await initPrivacyConfig(provider as anchor.AnchorProvider, program, owner);
+    await initVaultStats(provider as anchor.AnchorProvider, program, owner);
 
     const mxePublicKey = await getMXEPublicKeyWithRetry(
       provider as anchor.AnchorProvider,
@@ -79,40 +324,52 @@ describe("IapVault", () => {
     const sharedSecret = x25519.getSharedSecret(privateKey, mxePublicKey);
     const cipher = new RescueCipher(sharedSecret);
 
-    const val1 = BigInt(1);
-    const val2 = BigInt(2);
-    const plaintext = [val1, val2];
+    // tier, nftCount, solBalanceLamports, tradingVolumeCents, tokenHoldings, defiInteractions
+    const plaintext = [
+      BigInt(1),
+      BigInt(25),
+      BigInt(12_000_000_000),
+      BigInt(150_000),
+      BigInt(7),
+      BigInt(3),
+    ];
 
     const nonce = randomBytes(16);
     const ciphertext = cipher.encrypt(plaintext, nonce);
 
-    const sumEventPromise = awaitEvent("sumEvent");
+    const snapshotSlot = new anchor.BN(
+      await provider.connection.getSlot("confirmed"),
+    );
+    const attestation = createProfileAttestationIx(
+      owner,
+      profileAttestationMessage(
+        owner.publicKey,
+        publicKey,
+        ciphertext,
+        nonce,
+        snapshotSlot,
+      ),
+    );
+
+    const profileStoredPromise = awaitEvent("profileStored");
     const computationOffset = new anchor.BN(randomBytes(8), "hex");
 
     const queueSig = await program.methods
-      .addTogether(
+      .storeInterestProfile(
         computationOffset,
-        Array.from(ciphertext[0]),
-        Array.from(ciphertext[1]),
+        ciphertext.map((c) => Array.from(c)),
         Array.from(publicKey),
         new anchor.BN(deserializeLE(nonce).toString()),
+        snapshotSlot,
       )
       .accountsPartial({
-        computationAccount: getComputationAccAddress(
-          arciumEnv.arciumClusterOffset,
-          computationOffset,
-        ),
-        clusterAccount,
-        mxeAccount: getMXEAccAddress(program.programId),
-        mempoolAccount: getMempoolAccAddress(arciumEnv.arciumClusterOffset),
-        executingPool: getExecutingPoolAccAddress(
-          arciumEnv.arciumClusterOffset,
-        ),
-        compDefAccount: getCompDefAccAddress(
-          program.programId,
-          Buffer.from(getCompDefAccOffset("add_together")).readUInt32LE(),
-        ),
+        user: owner.publicKey,
+        // Fees are paid to the vault admin, which is the test wallet
+        feeRecipient: owner.publicKey,
+        ...arciumAccounts(computationOffset, "store_interest_profile"),
       })
+      .preInstructions([attestation])
+      .signers([owner])
       .rpc({ skipPreflight: true, commitment: "confirmed" });
     console.log("Queue sig is ", queueSig);
 
@@ -124,69 +381,1142 @@ describe("IapVault", () => {
     );
     console.log("Finalize sig is ", finalizeSig);
 
-    const sumEvent = await sumEventPromise;
-    const decrypted = cipher.decrypt([sumEvent.sum], sumEvent.nonce)[0];
-    expect(decrypted).to.equal(val1 + val2);
+    const profileStored = await profileStoredPromise;
+    expect(profileStored.owner.toBase58()).to.equal(owner.publicKey.toBase58());
+    expect(profileStored.computationOffset.toString()).to.equal(
+      computationOffset.toString(),
+    );
+    expect(profileStored.attestedBy.toBase58()).to.equal(
+      owner.publicKey.toBase58(),
+    );
+
+    const userProfile = await program.account.userProfile.fetch(
+      userProfilePda,
+    );
+    // The vault now holds an MXE ciphertext, never the user's own ciphertext
+    const stored = userProfile.interestProfile.inner.ciphertexts;
+    expect(stored).to.have.length(6);
+    expect(Buffer.from(stored[1])).to.not.deep.equal(
+      Buffer.from(ciphertext[1]),
+    );
+    expect(userProfile.tierPolicyVersion).to.equal(1);
+    expect(userProfile.snapshotSlot.toString()).to.equal(
+      snapshotSlot.toString(),
+    );
   });
 
-  async function initAddTogetherCompDef(
-    program: Program<IapVault>,
-    owner: anchor.web3.Keypair,
-    uploadRawCircuit: boolean,
-    offchainSource: boolean,
-  ): Promise<string> {
-    const baseSeedCompDefAcc = getArciumAccountBaseSeed(
-      "ComputationDefinitionAccount",
+  it("Reveals only eligibility for advertiser criteria", async () => {
+    // 25 NFTs and 12 SOL: SILVER under the default tier policy
+    const criteria = {
+      minTier: 1,
+      minNftCount: 20,
+      minSolLamports: new anchor.BN(10_000_000_000),
+      minTradingVolumeCents: new anchor.BN(0),
+      minTokenHoldings: 0,
+      minDefiInteractions: 0,
+    };
+
+    // The owner allows one check; the requester picks the criteria
+    const expiresAtSlot = new anchor.BN(
+      (await provider.connection.getSlot("confirmed")) + 10_000,
     );
-    const offset = getCompDefAccOffset("add_together");
+    await program.methods
+      .grantEligibilityConsent(owner.publicKey, 1, expiresAtSlot)
+      .accountsPartial({ user: owner.publicKey })
+      .signers([owner])
+      .rpc({ commitment: "confirmed" });
 
-    const compDefPDA = PublicKey.findProgramAddressSync(
-      [baseSeedCompDefAcc, program.programId.toBuffer(), offset],
-      getArciumProgramId(),
-    )[0];
+    const eligibilityPromise = awaitEvent("eligibilityChecked");
+    const computationOffset = new anchor.BN(randomBytes(8), "hex");
 
-    console.log("Comp def pda is ", compDefPDA);
+    await program.methods
+      .checkEligibility(computationOffset, criteria)
+      .accountsPartial({
+        requester: owner.publicKey,
+        userProfile: userProfilePda,
+        feeRecipient: owner.publicKey,
+        ...arciumAccounts(computationOffset, "check_eligibility"),
+      })
+      .signers([owner])
+      .rpc({ skipPreflight: true, commitment: "confirmed" });
 
-    const sig = await program.methods
-      .initAddTogetherCompDef()
-      .accounts({
-        compDefAccount: compDefPDA,
-        payer: owner.publicKey,
-        mxeAccount: getMXEAccAddress(program.programId),
+    await awaitComputationFinalization(
+      provider as anchor.AnchorProvider,
+      computationOffset,
+      program.programId,
+      "confirmed",
+    );
+
+    const eligibility = await eligibilityPromise;
+    expect(eligibility.owner.toBase58()).to.equal(owner.publicKey.toBase58());
+    expect(eligibility.eligible).to.equal(true);
+
+    // The single consented query is used up
+    const [consentPda] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("eligibility_consent"),
+        owner.publicKey.toBuffer(),
+        owner.publicKey.toBuffer(),
+      ],
+      program.programId,
+    );
+    const consent = await program.account.eligibilityConsent.fetch(consentPda);
+    expect(consent.queriesRemaining).to.equal(0);
+  });
+
+  it("Transfers the admin role in two steps", async () => {
+    const vaultConfig = getVaultConfigPda(program.programId);
+    const newAdmin = anchor.web3.Keypair.generate();
+    const stranger = anchor.web3.Keypair.generate();
+    const acceptAdmin = (pendingAdmin: anchor.web3.Keypair) =>
+      program.methods
+        .acceptAdmin()
+        .accountsPartial({ pendingAdmin: pendingAdmin.publicKey })
+        .signers([pendingAdmin])
+        .rpc({ commitment: "confirmed" });
+
+    await proposeAdmin(program, owner, newAdmin.publicKey);
+    await expectError(acceptAdmin(stranger), "Unauthorized");
+
+    await program.methods
+      .cancelAdminTransfer()
+      .accountsPartial({ admin: owner.publicKey })
+      .signers([owner])
+      .rpc({ commitment: "confirmed" });
+    let config = await program.account.vaultConfig.fetch(vaultConfig);
+    expect(config.pendingAdmin).to.equal(null);
+    await expectError(acceptAdmin(newAdmin), "Unauthorized");
+
+    await proposeAdmin(program, owner, newAdmin.publicKey);
+    await acceptAdmin(newAdmin);
+    config = await program.account.vaultConfig.fetch(vaultConfig);
+    expect(config.admin.toBase58()).to.equal(newAdmin.publicKey.toBase58());
+    expect(config.pendingAdmin).to.equal(null);
+
+    // The config accounts follow the vault admin
+    const updatePrivacyConfig = (admin: anchor.web3.Keypair) =>
+      program.methods
+        .updatePrivacyConfig(DEFAULT_PRIVACY_PARAMS)
+        .accountsPartial({ admin: admin.publicKey })
+        .signers([admin])
+        .rpc({ commitment: "confirmed" });
+    await expectError(updatePrivacyConfig(owner), "Unauthorized");
+    await updatePrivacyConfig(newAdmin);
+
+    // Hand the role back for the remaining tests
+    await proposeAdmin(program, newAdmin, owner.publicKey);
+    await acceptAdmin(owner);
+    config = await program.account.vaultConfig.fetch(vaultConfig);
+    expect(config.admin.toBase58()).to.equal(owner.publicKey.toBase58());
+  });
+
+  it("Stores encrypted campaign criteria once", async () => {
+    await airdrop(advertiser.publicKey, 10);
+
+    // Same minimums as the eligibility check: the stored profile matches
+    const criteria = await encryptForMxe([
+      BigInt(1),
+      BigInt(20),
+      BigInt(10_000_000_000),
+      BigInt(0),
+      BigInt(0),
+      BigInt(0),
+    ]);
+    const storeCriteria = (computationOffset: anchor.BN) =>
+      program.methods
+        .storeCampaignCriteria(
+          computationOffset,
+          new anchor.BN(criteriaId),
+          criteria.ciphertexts,
+          criteria.publicKey,
+          criteria.nonce,
+        )
+        .accountsPartial({
+          advertiser: advertiser.publicKey,
+          campaignCriteria: criteriaPda,
+          advertiserState: advertiserStatePda,
+          ...arciumAccounts(computationOffset, "store_campaign_criteria"),
+        })
+        .signers([advertiser]);
+
+    const criteriaStoredPromise = awaitEvent("campaignCriteriaStored");
+    const storeOffset = new anchor.BN(randomBytes(8), "hex");
+    await storeCriteria(storeOffset).rpc({
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    await finalize(storeOffset);
+    const criteriaStored = await criteriaStoredPromise;
+    expect(criteriaStored.criteria.toBase58()).to.equal(criteriaPda.toBase58());
+
+    // Stored criteria are immutable and count against the advertiser's limit
+    await expectError(
+      storeCriteria(new anchor.BN(randomBytes(8), "hex")).rpc({
+        commitment: "confirmed",
+      }),
+      "CriteriaAlreadyStored",
+    );
+    const advertiserState = await program.account.advertiserState.fetch(
+      advertiserStatePda,
+    );
+    expect(advertiserState.criteriaCount).to.equal(1);
+  });
+
+  it("Matches a profile against the criteria of a live campaign", async () => {
+    const reward = new anchor.BN(1_000);
+    const campaign = await createCampaign(3, reward, 1, reward);
+    const consent = pda(
+      Buffer.from("eligibility_consent"),
+      owner.publicKey.toBuffer(),
+      advertiser.publicKey.toBuffer(),
+    );
+    const match = (computationOffset: anchor.BN) =>
+      program.methods
+        .matchCampaignCriteria(computationOffset)
+        .accountsPartial({
+          advertiser: advertiser.publicKey,
+          feeRecipient: owner.publicKey,
+          userProfile: userProfilePda,
+          eligibilityConsent: consent,
+          campaign,
+          campaignCriteria: criteriaPda,
+          ...arciumAccounts(computationOffset, "match_campaign_criteria"),
+        })
+        .signers([advertiser]);
+
+    // Nothing is matched without the owner's consent
+    await expectError(
+      match(new anchor.BN(randomBytes(8), "hex")).rpc({
+        commitment: "confirmed",
+      }),
+      "AccountNotInitialized",
+    );
+    const expiresAtSlot = new anchor.BN(
+      (await provider.connection.getSlot("confirmed")) + 10_000,
+    );
+    await program.methods
+      .grantEligibilityConsent(advertiser.publicKey, 1, expiresAtSlot)
+      .accountsPartial({ user: owner.publicKey })
+      .signers([owner])
+      .rpc({ commitment: "confirmed" });
+
+    const matchedPromise = awaitEvent("campaignCriteriaMatched");
+    const matchOffset = new anchor.BN(randomBytes(8), "hex");
+    await match(matchOffset).rpc({
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    await finalize(matchOffset);
+    const matched = await matchedPromise;
+    expect(matched.owner.toBase58()).to.equal(owner.publicKey.toBase58());
+    expect(matched.matched).to.equal(true);
+
+    // The consent allowed a single match
+    await expectError(
+      match(new anchor.BN(randomBytes(8), "hex")).rpc({
+        commitment: "confirmed",
+      }),
+      "ConsentExhausted",
+    );
+
+    await closeCampaign(campaign);
+  });
+
+  it("Escrows campaign funding and pays a matching claim from it", async () => {
+    const reward = new anchor.BN(LAMPORTS_PER_SOL / 10);
+    const campaign = await createCampaign(1, reward, 10, reward.muln(2));
+    // Anyone may top up the escrow
+    await program.methods
+      .fundCampaign(reward)
+      .accountsPartial({ funder: owner.publicKey, campaign })
+      .signers([owner])
+      .rpc({ commitment: "confirmed" });
+
+    // The escrow is held as lamports of the campaign PDA, on top of its rent
+    let campaignAccount = await program.account.campaign.fetch(campaign);
+    expect(campaignAccount.escrowBalance.toString()).to.equal(
+      reward.muln(3).toString(),
+    );
+    let campaignInfo = await provider.connection.getAccountInfo(
+      campaign,
+      "confirmed",
+    );
+    const rent = await provider.connection.getMinimumBalanceForRentExemption(
+      campaignInfo.data.length,
+    );
+    expect(campaignInfo.lamports).to.equal(rent + reward.muln(3).toNumber());
+
+    const settledPromise = awaitEvent("rewardClaimSettled");
+    const claimOffset = new anchor.BN(randomBytes(8), "hex");
+    await claimReward(campaign, claimOffset).rpc({
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    await finalize(claimOffset);
+    const settled = await settledPromise;
+    expect(settled.status).to.deep.equal({ paid: {} });
+    expect(settled.rewardPaid.toString()).to.equal(reward.toString());
+    expect(settled.transferFee.toNumber()).to.equal(0);
+
+    campaignAccount = await program.account.campaign.fetch(campaign);
+    expect(campaignAccount.participants).to.equal(1);
+    expect(campaignAccount.pendingClaims).to.equal(0);
+    expect(campaignAccount.reservedBalance.toNumber()).to.equal(0);
+    expect(campaignAccount.escrowBalance.toString()).to.equal(
+      reward.muln(2).toString(),
+    );
+    campaignInfo = await provider.connection.getAccountInfo(
+      campaign,
+      "confirmed",
+    );
+    expect(campaignInfo.lamports).to.equal(rent + reward.muln(2).toNumber());
+
+    // One claim per user and campaign
+    await expectError(
+      claimReward(campaign, new anchor.BN(randomBytes(8), "hex")).rpc({
+        commitment: "confirmed",
+      }),
+      "AlreadyClaimed",
+    );
+
+    // Closing refunds the unspent escrow and the rent to the advertiser
+    const advertiserBefore = await provider.connection.getBalance(
+      advertiser.publicKey,
+      "confirmed",
+    );
+    const closedPromise = awaitEvent("campaignClosed");
+    await closeCampaign(campaign);
+    const closed = await closedPromise;
+    expect(closed.refundedBalance.toString()).to.equal(
+      reward.muln(2).toString(),
+    );
+    expect(closed.participants).to.equal(1);
+    const advertiserAfter = await provider.connection.getBalance(
+      advertiser.publicKey,
+      "confirmed",
+    );
+    expect(advertiserAfter - advertiserBefore).to.equal(
+      rent + reward.muln(2).toNumber(),
+    );
+    expect(
+      await provider.connection.getAccountInfo(campaign, "confirmed"),
+    ).to.equal(null);
+  });
+
+  it("Pays token campaign rewards net of the Token-2022 transfer fee", async () => {
+    const connection = provider.connection;
+    const tokenProgram = TOKEN_2022_PROGRAM_ID;
+    // 1% of every transfer is withheld in the receiving account
+    const feeBasisPoints = 100;
+    const fee = (amount: number) =>
+      Math.ceil((amount * feeBasisPoints) / 10_000);
+
+    const mint = anchor.web3.Keypair.generate();
+    const mintLen = getMintLen([ExtensionType.TransferFeeConfig]);
+    await (provider as anchor.AnchorProvider).sendAndConfirm(
+      new anchor.web3.Transaction().add(
+        SystemProgram.createAccount({
+          fromPubkey: owner.publicKey,
+          newAccountPubkey: mint.publicKey,
+          space: mintLen,
+          lamports: await connection.getMinimumBalanceForRentExemption(mintLen),
+          programId: tokenProgram,
+        }),
+        createInitializeTransferFeeConfigInstruction(
+          mint.publicKey,
+          owner.publicKey,
+          owner.publicKey,
+          feeBasisPoints,
+          BigInt(1_000_000_000),
+          tokenProgram,
+        ),
+        createInitializeMintInstruction(
+          mint.publicKey,
+          6,
+          owner.publicKey,
+          null,
+          tokenProgram,
+        ),
+      ),
+      [mint],
+      { commitment: "confirmed" },
+    );
+    const tokenAccount = (holder: PublicKey) =>
+      createAssociatedTokenAccountIdempotent(
+        connection,
+        owner,
+        mint.publicKey,
+        holder,
+        { commitment: "confirmed" },
+        tokenProgram,
+      );
+    const balance = async (account: PublicKey) =>
+      Number(
+        (await getAccount(connection, account, "confirmed", tokenProgram))
+          .amount,
+      );
+    const advertiserTokenAccount = await tokenAccount(advertiser.publicKey);
+    const claimantTokenAccount = await tokenAccount(owner.publicKey);
+    await mintTo(
+      connection,
+      owner,
+      mint.publicKey,
+      advertiserTokenAccount,
+      owner,
+      10_000_000,
+      [],
+      { commitment: "confirmed" },
+      tokenProgram,
+    );
+
+    const campaignId = 2;
+    const campaign = campaignPda(campaignId);
+    const escrowVault = getAssociatedTokenAddressSync(
+      mint.publicKey,
+      campaign,
+      true,
+      tokenProgram,
+    );
+    const funding = 1_000_000;
+    const reward = 100_000;
+    const slot = await connection.getSlot("confirmed");
+    await program.methods
+      .createTokenCampaign(
+        new anchor.BN(campaignId),
+        new anchor.BN(reward),
+        new anchor.BN(slot),
+        new anchor.BN(slot + 10_000),
+        5,
+        new anchor.BN(funding),
+      )
+      .accountsPartial({
+        advertiser: advertiser.publicKey,
+        campaign,
+        campaignCriteria: criteriaPda,
+        rewardMint: mint.publicKey,
+        escrowVault,
+        advertiserTokenAccount,
+        tokenProgram,
       })
+      .signers([advertiser])
+      .rpc({ commitment: "confirmed" });
+
+    // The escrow is credited with what the vault received, not what was sent
+    const funded = funding - fee(funding);
+    let campaignAccount = await program.account.campaign.fetch(campaign);
+    expect(campaignAccount.escrowBalance.toNumber()).to.equal(funded);
+    expect(await balance(escrowVault)).to.equal(funded);
+
+    // A payout the token program would refuse, here to an account closed before
+    // the callback, settles the claim as Failed and releases its reservation
+    const closedTokenAccount = await createAccount(
+      connection,
+      owner,
+      mint.publicKey,
+      owner.publicKey,
+      anchor.web3.Keypair.generate(),
+      { commitment: "confirmed" },
+      tokenProgram,
+    );
+    const failedPromise = awaitEvent("rewardClaimSettled");
+    const failedOffset = new anchor.BN(randomBytes(8), "hex");
+    await claimReward(campaign, failedOffset, {
+      rewardMint: mint.publicKey,
+      escrowVault,
+      claimantTokenAccount: closedTokenAccount,
+      tokenProgram,
+    })
+      .postInstructions([
+        createCloseAccountInstruction(
+          closedTokenAccount,
+          owner.publicKey,
+          owner.publicKey,
+          [],
+          tokenProgram,
+        ),
+      ])
+      .rpc({ skipPreflight: true, commitment: "confirmed" });
+    await finalize(failedOffset);
+    expect((await failedPromise).status).to.deep.equal({ failed: {} });
+    campaignAccount = await program.account.campaign.fetch(campaign);
+    expect(campaignAccount.pendingClaims).to.equal(0);
+    expect(campaignAccount.reservedBalance.toNumber()).to.equal(0);
+    expect(campaignAccount.escrowBalance.toNumber()).to.equal(funded);
+
+    // The claim can be retried to a working account
+    const settledPromise = awaitEvent("rewardClaimSettled");
+    const claimOffset = new anchor.BN(randomBytes(8), "hex");
+    await claimReward(campaign, claimOffset, {
+      rewardMint: mint.publicKey,
+      escrowVault,
+      claimantTokenAccount,
+      tokenProgram,
+    }).rpc({ skipPreflight: true, commitment: "confirmed" });
+    await finalize(claimOffset);
+    const settled = await settledPromise;
+    expect(settled.status).to.deep.equal({ paid: {} });
+    expect(settled.rewardPaid.toNumber()).to.equal(reward);
+    expect(settled.transferFee.toNumber()).to.equal(fee(reward));
+    expect(await balance(claimantTokenAccount)).to.equal(reward - fee(reward));
+
+    const remaining = funded - reward;
+    campaignAccount = await program.account.campaign.fetch(campaign);
+    expect(campaignAccount.participants).to.equal(1);
+    expect(campaignAccount.escrowBalance.toNumber()).to.equal(remaining);
+    expect(await balance(escrowVault)).to.equal(remaining);
+
+    // The whole vault goes back to the advertiser, minus the transfer fee
+    const advertiserBefore = await balance(advertiserTokenAccount);
+    await program.methods
+      .closeTokenCampaign()
+      .accountsPartial({
+        advertiser: advertiser.publicKey,
+        campaign,
+        rewardMint: mint.publicKey,
+        escrowVault,
+        advertiserTokenAccount,
+        tokenProgram,
+      })
+      .signers([advertiser])
+      .rpc({ commitment: "confirmed" });
+    expect(await balance(advertiserTokenAccount)).to.equal(
+      advertiserBefore + remaining - fee(remaining),
+    );
+    expect(await connection.getAccountInfo(escrowVault, "confirmed")).to.equal(
+      null,
+    );
+    expect(await connection.getAccountInfo(campaign, "confirmed")).to.equal(
+      null,
+    );
+  });
+
+  it("Closes settled claim records once their campaign is over", async () => {
+    const reward = new anchor.BN(1_000);
+    const campaign = await createCampaign(4, reward, 1, reward);
+    const closeClaimRecord = (campaign: PublicKey) =>
+      program.methods
+        .closeClaimRecord()
+        .accountsPartial({
+          user: owner.publicKey,
+          claimRecord: claimRecordPda(campaign, owner.publicKey),
+          campaign,
+        })
+        .signers([owner])
+        .rpc({ commitment: "confirmed" });
+
+    const claimOffset = new anchor.BN(randomBytes(8), "hex");
+    await claimReward(campaign, claimOffset).rpc({
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    await expectError(closeClaimRecord(campaign), "ClaimRecordInUse");
+    // Nobody can give up on a claim before CLAIM_TIMEOUT_SLOTS
+    await expectError(
+      program.methods
+        .expirePendingClaim()
+        .accountsPartial({
+          campaign,
+          claimRecord: claimRecordPda(campaign, owner.publicKey),
+        })
+        .rpc({ commitment: "confirmed" }),
+      "ClaimNotExpired",
+    );
+    await finalize(claimOffset);
+
+    // Settled, but the record is what blocks a second claim while the
+    // campaign runs
+    await expectError(closeClaimRecord(campaign), "ClaimRecordInUse");
+
+    await closeCampaign(campaign);
+    await closeClaimRecord(campaign);
+    expect(
+      await provider.connection.getAccountInfo(
+        claimRecordPda(campaign, owner.publicKey),
+        "confirmed",
+      ),
+    ).to.equal(null);
+
+    // Campaigns closed earlier release their records as well
+    await closeClaimRecord(campaignPda(1));
+    expect(
+      await provider.connection.getAccountInfo(
+        claimRecordPda(campaignPda(1), owner.publicKey),
+        "confirmed",
+      ),
+    ).to.equal(null);
+  });
+
+  it("Suppresses a noised audience estimate below the k-anonymity floor", async () => {
+    const audienceEstimate = pda(
+      Buffer.from("audience_estimate"),
+      criteriaPda.toBuffer(),
+    );
+    const estimate = (computationOffset: anchor.BN) =>
+      program.methods
+        .estimateAudience(computationOffset)
+        .accountsPartial({
+          advertiser: advertiser.publicKey,
+          campaignCriteria: criteriaPda,
+          audienceEstimate,
+          ...arciumAccounts(computationOffset, "estimate_audience"),
+        })
+        .remainingAccounts([
+          { pubkey: userProfilePda, isSigner: false, isWritable: false },
+        ])
+        .signers([advertiser]);
+
+    const countedPromise = awaitEvent("audienceBatchCounted");
+    const estimateOffset = new anchor.BN(randomBytes(8), "hex");
+    await estimate(estimateOffset).rpc({
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    await finalize(estimateOffset);
+    expect((await countedPromise).profilesScanned).to.equal(1);
+
+    // The reveal is charged to a campaign on the estimated criteria
+    const reward = new anchor.BN(1_000);
+    const campaign = await createCampaign(5, reward, 1, reward);
+
+    const estimatedPromise = awaitEvent("audienceEstimated");
+    const revealOffset = new anchor.BN(randomBytes(8), "hex");
+    await program.methods
+      .revealAudienceEstimate(revealOffset)
+      .accountsPartial({
+        advertiser: advertiser.publicKey,
+        audienceEstimate,
+        campaign,
+        ...arciumAccounts(revealOffset, "reveal_audience_estimate"),
+      })
+      .signers([advertiser])
+      .rpc({ skipPreflight: true, commitment: "confirmed" });
+    await finalize(revealOffset);
+    const estimated = await estimatedPromise;
+    // One profile is far below the k-anonymity floor, so only that is revealed
+    expect(estimated.kAnonymityFloor).to.equal(
+      DEFAULT_PRIVACY_PARAMS.kAnonymityFloor,
+    );
+    expect(estimated.meetsFloor).to.equal(false);
+    expect(estimated.audienceSize).to.equal(0);
+
+    // The epsilon is charged to the campaign's own budget
+    const campaignState = await program.account.campaign.fetch(campaign);
+    expect(campaignState.privacySpentMillis).to.equal(
+      DEFAULT_PRIVACY_PARAMS.epsilonMillis,
+    );
+
+    // A revealed estimate is final
+    await expectError(
+      estimate(new anchor.BN(randomBytes(8), "hex")).rpc({
+        commitment: "confirmed",
+      }),
+      "EstimateFinalized",
+    );
+  });
+
+  it("Aggregates and reveals the tier histogram as the vault admin", async () => {
+    await expectError(
+      aggregateTierHistogram(
+        anchor.web3.Keypair.generate(),
+        new anchor.BN(randomBytes(8), "hex"),
+      ).rpc({ commitment: "confirmed" }),
+      "Unauthorized",
+    );
+
+    const aggregatedPromise = awaitEvent("tierHistogramAggregated");
+    const aggregateOffset = new anchor.BN(randomBytes(8), "hex");
+    await aggregateTierHistogram(owner, aggregateOffset).rpc({
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    await finalize(aggregateOffset);
+    expect((await aggregatedPromise).profilesScanned).to.equal(1);
+
+    const revealedPromise = awaitEvent("tierHistogramRevealed");
+    const revealOffset = new anchor.BN(randomBytes(8), "hex");
+    await revealTierHistogram(revealOffset).rpc({
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    await finalize(revealOffset);
+    const revealed = await revealedPromise;
+    // The single SILVER profile is suppressed by the k-anonymity floor
+    expect(revealed.silver).to.equal(0);
+
+    // A new epoch counts every profile again
+    await program.methods
+      .resetVaultStats()
+      .accountsPartial({ admin: owner.publicKey, vaultStats })
       .signers([owner])
-      .rpc({
+      .rpc({ commitment: "confirmed" });
+    const stats = await program.account.vaultStats.fetch(vaultStats);
+    expect(stats.epoch).to.equal(2);
+    expect(stats.profilesScanned).to.equal(0);
+  });
+
+  it("Stops revealing the tier histogram once its budget is spent", async () => {
+    // The reveal above spent one epsilon, which outlives the epoch reset
+    const epsilon = DEFAULT_PRIVACY_PARAMS.epsilonMillis;
+    const stats = await program.account.vaultStats.fetch(vaultStats);
+    expect(stats.privacySpentMillis).to.equal(epsilon);
+
+    const updatePrivacyConfig = (params: typeof DEFAULT_PRIVACY_PARAMS) =>
+      program.methods
+        .updatePrivacyConfig(params)
+        .accountsPartial({ admin: owner.publicKey })
+        .signers([owner])
+        .rpc({ commitment: "confirmed" });
+    await updatePrivacyConfig({
+      ...DEFAULT_PRIVACY_PARAMS,
+      statsBudgetMillis: epsilon,
+    });
+
+    const aggregateOffset = new anchor.BN(randomBytes(8), "hex");
+    await aggregateTierHistogram(owner, aggregateOffset).rpc({
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    await finalize(aggregateOffset);
+    await expectError(
+      revealTierHistogram(new anchor.BN(randomBytes(8), "hex")).rpc({
         commitment: "confirmed",
-      });
-    console.log("Init add together computation definition transaction", sig);
+      }),
+      "PrivacyBudgetExhausted",
+    );
 
-    if (uploadRawCircuit) {
-      const rawCircuit = fs.readFileSync("build/add_together.arcis");
+    await updatePrivacyConfig(DEFAULT_PRIVACY_PARAMS);
+  });
 
-      await uploadCircuit(
-        provider as anchor.AnchorProvider,
-        "add_together",
-        program.programId,
-        rawCircuit,
+  it("Discloses only the fields the owner picks to a recipient", async () => {
+    // The recipient only gets the NFT count and the SOL balance
+    const recipient = await newReader();
+    const fieldMask = 0b000110;
+    const disclosedPromise = awaitEvent("profileFieldsDisclosed");
+    const discloseOffset = new anchor.BN(randomBytes(8), "hex");
+    await program.methods
+      .discloseFields(
+        discloseOffset,
+        recipient.publicKey,
+        randomNonce(),
+        fieldMask,
+      )
+      .accountsPartial({
+        user: owner.publicKey,
+        userProfile: userProfilePda,
+        ...arciumAccounts(discloseOffset, "disclose_fields"),
+      })
+      .signers([owner])
+      .rpc({ skipPreflight: true, commitment: "confirmed" });
+    await finalize(discloseOffset);
+    const disclosed = await disclosedPromise;
+    expect(disclosed.fieldMask).to.equal(fieldMask);
+    expect(
+      recipient.cipher.decrypt(
+        disclosed.ciphertexts,
+        nonceBytes(disclosed.nonce),
+      ),
+    ).to.deep.equal([
+      BigInt(0),
+      BigInt(25),
+      BigInt(12_000_000_000),
+      BigInt(0),
+      BigInt(0),
+      BigInt(0),
+    ]);
+  });
+
+  it("Re-encrypts the stored profile to its owner", async () => {
+    // The owner reads back the whole profile, with the MPC-derived SILVER tier
+    const reader = await newReader();
+    const readBackPromise = awaitEvent("profileReadBack");
+    const readOffset = new anchor.BN(randomBytes(8), "hex");
+    await program.methods
+      .readMyProfile(readOffset, reader.publicKey, randomNonce())
+      .accountsPartial({
+        user: owner.publicKey,
+        userProfile: userProfilePda,
+        ...arciumAccounts(readOffset, "read_my_profile"),
+      })
+      .signers([owner])
+      .rpc({ skipPreflight: true, commitment: "confirmed" });
+    await finalize(readOffset);
+    const readBack = await readBackPromise;
+    expect(
+      reader.cipher.decrypt(readBack.ciphertexts, nonceBytes(readBack.nonce)),
+    ).to.deep.equal([
+      BigInt(1),
+      BigInt(25),
+      BigInt(12_000_000_000),
+      BigInt(150_000),
+      BigInt(7),
+      BigInt(3),
+    ]);
+  });
+
+  it("Stores the median of an oracle quorum once the owner opens a round", async () => {
+    await airdrop(quorumUser.publicKey, 2);
+    const oracleRegistry = getOracleRegistryPda(program.programId);
+    const oracles = [0, 1, 2].map(() => anchor.web3.Keypair.generate());
+    for (const oracle of oracles) {
+      await addOracle(program, owner, oracle.publicKey);
+    }
+
+    // Rounds take up to 3 submissions and can be finalized from 2 on
+    const setQuorum = (threshold: number, size: number) =>
+      program.methods
+        .setOracleQuorum(threshold, size)
+        .accountsPartial({ admin: owner.publicKey, oracleRegistry })
+        .signers([owner])
+        .rpc({ commitment: "confirmed" });
+    await expectError(setQuorum(3, 2), "InvalidQuorumConfig");
+    await expectError(setQuorum(2, 6), "InvalidQuorumConfig");
+    await setQuorum(2, 3);
+
+    // Snapshots of one wallet; the tier is derived inside MPC, not submitted
+    const snapshots = [
+      [0, 30, 15_000_000_000, 200_000, 5, 4],
+      [0, 20, 11_000_000_000, 100_000, 9, 2],
+      [0, 25, 12_000_000_000, 150_000, 7, 3],
+    ];
+    const submit = async (oracle: anchor.web3.Keypair, snapshot: number[]) => {
+      const input = await encryptForMxe(snapshot.map(BigInt));
+      const snapshotSlot = new anchor.BN(
+        await provider.connection.getSlot("confirmed"),
+      );
+      return program.methods
+        .submitOracleProfile(
+          quorumUser.publicKey,
+          input.ciphertexts,
+          input.publicKey,
+          input.nonce,
+          snapshotSlot,
+        )
+        .accountsPartial({
+          oracle: oracle.publicKey,
+          pendingSubmission: quorumSubmission,
+        })
+        .signers([oracle])
+        .rpc({ commitment: "confirmed" });
+    };
+
+    // Oracles cannot start a round, or create accounts, for the owner
+    await expectError(
+      submit(oracles[0], snapshots[0]),
+      "AccountNotInitialized",
+    );
+
+    await program.methods
+      .openQuorumRound()
+      .accountsPartial({
+        user: quorumUser.publicKey,
+        userProfile: quorumUserProfile,
+        pendingSubmission: quorumSubmission,
+      })
+      .signers([quorumUser])
+      .rpc({ commitment: "confirmed" });
+    const finalizeQuorum = (computationOffset: anchor.BN) =>
+      program.methods
+        .finalizeOracleQuorum(computationOffset)
+        .accountsPartial({
+          payer: owner.publicKey,
+          pendingSubmission: quorumSubmission,
+          userProfile: quorumUserProfile,
+          ...arciumAccounts(computationOffset, "median_interest_profile"),
+        })
+        .signers([owner]);
+
+    await submit(oracles[0], snapshots[0]);
+    await expectError(
+      finalizeQuorum(new anchor.BN(randomBytes(8), "hex")).rpc({
+        commitment: "confirmed",
+      }),
+      "QuorumIncomplete",
+    );
+    await submit(oracles[1], snapshots[1]);
+    await submit(oracles[2], snapshots[2]);
+    await expectError(submit(oracles[0], snapshots[0]), "QuorumComplete");
+
+    const finalizeOffset = new anchor.BN(randomBytes(8), "hex");
+    await finalizeQuorum(finalizeOffset).rpc({
+      skipPreflight: true,
+      commitment: "confirmed",
+    });
+    await finalize(finalizeOffset);
+
+    // Attested by the oracle with the oldest snapshot, the first one submitted
+    const registry = await program.account.oracleRegistry.fetch(oracleRegistry);
+    const oldest = registry.oracles.find((entry) =>
+      entry.oracle.equals(oracles[0].publicKey),
+    );
+    const profile = await program.account.userProfile.fetch(quorumUserProfile);
+    expect(profile.oracleId).to.equal(oldest.oracleId);
+    expect(profile.attestedBy.equals(oracles[0].publicKey)).to.equal(true);
+    expect(profile.interestProfile.inner.nonce.isZero()).to.equal(false);
+    expect(profile.tierPolicyVersion).to.equal(1);
+    const submission = await program.account.pendingProfileSubmission.fetch(
+      quorumSubmission,
+    );
+    expect(submission.roundOpen).to.equal(false);
+    expect(submission.count).to.equal(0);
+
+    // The round is over until the owner opens the next one
+    await expectError(submit(oracles[0], snapshots[2]), "QuorumRoundNotOpen");
+
+    // Like any profile, it goes stale once its attesting oracle is removed
+    await program.methods
+      .removeOracle(oldest.oracleId)
+      .accountsPartial({ admin: owner.publicKey, oracleRegistry })
+      .signers([owner])
+      .rpc({ commitment: "confirmed" });
+    await program.methods
+      .flagStaleProfile()
+      .accountsPartial({ userProfile: quorumUserProfile, oracleRegistry })
+      .rpc({ commitment: "confirmed" });
+    const flagged = await program.account.userProfile.fetch(quorumUserProfile);
+    expect(flagged.stale).to.equal(true);
+  });
+
+  it("Deletes a profile together with its oracle submissions and consents", async () => {
+    const expiresAtSlot = new anchor.BN(
+      (await provider.connection.getSlot("confirmed")) + 10_000,
+    );
+    await program.methods
+      .grantEligibilityConsent(advertiser.publicKey, 1, expiresAtSlot)
+      .accountsPartial({ user: quorumUser.publicKey })
+      .signers([quorumUser])
+      .rpc({ commitment: "confirmed" });
+    const consent = pda(
+      Buffer.from("eligibility_consent"),
+      quorumUser.publicKey.toBuffer(),
+      advertiser.publicKey.toBuffer(),
+    );
+    const closeProfile = (consents: PublicKey[]) =>
+      program.methods
+        .closeInterestProfile()
+        .accountsPartial({
+          user: quorumUser.publicKey,
+          userProfile: quorumUserProfile,
+          pendingSubmission: quorumSubmission,
+        })
+        .remainingAccounts(
+          consents.map((pubkey) => ({
+            pubkey,
+            isSigner: false,
+            isWritable: true,
+          })),
+        )
+        .signers([quorumUser])
+        .rpc({ commitment: "confirmed" });
+
+    // Only the owner's own consents can be closed with the profile
+    const ownerConsent = pda(
+      Buffer.from("eligibility_consent"),
+      owner.publicKey.toBuffer(),
+      advertiser.publicKey.toBuffer(),
+    );
+    await expectError(closeProfile([ownerConsent]), "ConsentMismatch");
+
+    await closeProfile([consent]);
+    for (const account of [quorumUserProfile, quorumSubmission, consent]) {
+      expect(
+        await provider.connection.getAccountInfo(account, "confirmed"),
+      ).to.equal(null);
+    }
+  });
+
+  it("Migrates baseline and store_tier profiles in place", async () => {
+    // Both are loaded into the validator from tests/fixtures (see Anchor.toml)
+    const loadFixture = async (name: string, length: number) => {
+      const user = readKpJson(`${__dirname}/fixtures/${name}_owner.json`);
+      const userProfile = pda(
+        Buffer.from("user_profile"),
+        user.publicKey.toBuffer(),
+      );
+      const info = await provider.connection.getAccountInfo(
+        userProfile,
+        "confirmed",
+      );
+      expect(info.data.length).to.equal(length);
+      // Pays for the realloc
+      await airdrop(user.publicKey, 1);
+      return { user, userProfile };
+    };
+    const migrate = (user: anchor.web3.Keypair) =>
+      program.methods
+        .migrateProfile()
+        .accountsPartial({ user: user.publicKey })
+        .signers([user])
+        .rpc({ commitment: "confirmed" });
+    const migrateFixture = async ({
+      user,
+      userProfile,
+    }: {
+      user: anchor.web3.Keypair;
+      userProfile: PublicKey;
+    }) => {
+      const migratedPromise = awaitEvent("profileMigrated");
+      await migrate(user);
+      const migrated = await migratedPromise;
+      expect(migrated.owner.toBase58()).to.equal(user.publicKey.toBase58());
+      expect(migrated.fromVersion).to.equal(0);
+      expect(migrated.toVersion).to.equal(3);
+
+      const info = await provider.connection.getAccountInfo(
+        userProfile,
+        "confirmed",
+      );
+      // UserProfile::SPACE
+      expect(info.data.length).to.equal(414);
+      const profile = await program.account.userProfile.fetch(userProfile);
+      expect(profile.tierPolicyVersion).to.equal(0);
+      expect(profile.owner.toBase58()).to.equal(user.publicKey.toBase58());
+      expect(profile.lastInputNonce.toNumber()).to.equal(0);
+      expect(profile.pendingComputation.equals(PublicKey.default)).to.equal(
         true,
       );
-    } else if (!offchainSource) {
-      const finalizeTx = await buildFinalizeCompDefTx(
-        provider as anchor.AnchorProvider,
-        Buffer.from(offset).readUInt32LE(),
-        program.programId,
+      expect(profile.pendingComputationOffset.toNumber()).to.equal(0);
+      expect(profile.version).to.equal(3);
+      expect(profile.createdAt.toString()).to.equal(migrated.slot.toString());
+      // Never attested by an oracle
+      expect(profile.attestedBy.equals(PublicKey.default)).to.equal(true);
+      expect(profile.snapshotSlot.toNumber()).to.equal(0);
+      expect(profile.pendingAttestedBy.equals(PublicKey.default)).to.equal(
+        true,
       );
+      expect(profile.pendingSnapshotSlot.toNumber()).to.equal(0);
+      expect(profile.oracleId).to.equal(0);
+      expect(profile.pendingOracleId).to.equal(0);
+      expect(profile.stale).to.equal(false);
+      return { profile, slot: migrated.slot };
+    };
 
-      const latestBlockhash = await provider.connection.getLatestBlockhash();
-      finalizeTx.recentBlockhash = latestBlockhash.blockhash;
-      finalizeTx.lastValidBlockHeight = latestBlockhash.lastValidBlockHeight;
+    // Written by the original store_interest_profile: just the encrypted profile
+    const baseline = await loadFixture("baseline_profile", 216);
+    const { profile: baselineProfile, slot } = await migrateFixture(baseline);
+    // The ciphertexts are kept; the timestamps start at the migration
+    expect(baselineProfile.interestProfile.inner.nonce.toNumber()).to.equal(7);
+    baselineProfile.interestProfile.inner.ciphertexts.forEach((c, i) =>
+      expect(Buffer.from(c)).to.deep.equal(Buffer.alloc(32, i + 1)),
+    );
+    expect(baselineProfile.updatedAt.toString()).to.equal(slot.toString());
 
-      finalizeTx.sign(owner);
+    // Created by store_tier, which never wrote a profile into the account
+    const storeTier = await loadFixture("store_tier_profile", 88);
+    const { profile: storeTierProfile } = await migrateFixture(storeTier);
+    expect(storeTierProfile.interestProfile.inner.nonce.toNumber()).to.equal(0);
+    storeTierProfile.interestProfile.inner.ciphertexts.forEach((c) =>
+      expect(Buffer.from(c)).to.deep.equal(Buffer.alloc(32)),
+    );
+    expect(storeTierProfile.updatedAt.toNumber()).to.equal(0);
 
-      await provider.sendAndConfirm(finalizeTx);
-    }
-    return sig;
-  }
+    await expectError(migrate(baseline.user), "ProfileAlreadyMigrated");
+    await expectError(migrate(owner), "ProfileAlreadyMigrated");
+
+    // Never attested, so it stays out of queries until a new store
+    const expiresAtSlot = new anchor.BN(
+      (await provider.connection.getSlot("confirmed")) + 10_000,
+    );
+    await program.methods
+      .grantEligibilityConsent(baseline.user.publicKey, 1, expiresAtSlot)
+      .accountsPartial({ user: baseline.user.publicKey })
+      .signers([baseline.user])
+      .rpc({ commitment: "confirmed" });
+    const checkOffset = new anchor.BN(randomBytes(8), "hex");
+    await expectError(
+      program.methods
+        .checkEligibility(checkOffset, {
+          minTier: 0,
+          minNftCount: 0,
+          minSolLamports: new anchor.BN(0),
+          minTradingVolumeCents: new anchor.BN(0),
+          minTokenHoldings: 0,
+          minDefiInteractions: 0,
+        })
+        .accountsPartial({
+          requester: baseline.user.publicKey,
+          userProfile: baseline.userProfile,
+          feeRecipient: owner.publicKey,
+          ...arciumAccounts(checkOffset, "check_eligibility"),
+        })
+        .signers([baseline.user])
+        .rpc({ commitment: "confirmed" }),
+      "ProfileNotAttested",
+    );
+  });
+
+  it("Revokes an oracle's attestations when its key is rotated", async () => {
+    const oracleRegistry = getOracleRegistryPda(program.programId);
+    const { oracleId } = await program.account.userProfile.fetch(
+      userProfilePda,
+    );
+    const newKey = anchor.web3.Keypair.generate();
+
+    const rotatedPromise = awaitEvent("oracleKeyRotated");
+    await program.methods
+      .rotateOracleKey(oracleId, newKey.publicKey, true)
+      .accountsPartial({ admin: owner.publicKey, oracleRegistry })
+      .signers([owner])
+      .rpc({ commitment: "confirmed" });
+    const rotated = await rotatedPromise;
+    expect(rotated.oracleId).to.equal(oracleId);
+    expect(rotated.newOracleId).to.not.equal(oracleId);
+    const registry = await program.account.oracleRegistry.fetch(oracleRegistry);
+    const entry = registry.oracles.find((e) =>
+      e.oracle.equals(newKey.publicKey),
+    );
+    expect(entry.oracleId).to.equal(rotated.newOracleId);
+
+    // Everything attested under the old id can now be flagged, by anyone
+    const flagStale = () =>
+      program.methods
+        .flagStaleProfile()
+        .accountsPartial({ userProfile: userProfilePda, oracleRegistry })
+        .rpc({ commitment: "confirmed" });
+    await flagStale();
+    const profile = await program.account.userProfile.fetch(userProfilePda);
+    expect(profile.stale).to.equal(true);
+    await expectError(flagStale(), "ProfileNotStale");
+  });
+
+  it("Keeps consents revocable while queries are paused", async () => {
+    const setPauseFlags = (query: boolean) =>
+      program.methods
+        .setPauseFlags({ store: false, query, campaigns: false })
+        .accountsPartial({ admin: owner.publicKey })
+        .signers([owner])
+        .rpc({ commitment: "confirmed" });
+    const expiresAtSlot = new anchor.BN(
+      (await provider.connection.getSlot("confirmed")) + 10_000,
+    );
+    const grant = (requester: PublicKey) =>
+      program.methods
+        .grantEligibilityConsent(requester, 1, expiresAtSlot)
+        .accountsPartial({ user: owner.publicKey })
+        .signers([owner])
+        .rpc({ commitment: "confirmed" });
+    const requester = anchor.web3.Keypair.generate().publicKey;
+    await grant(requester);
+
+    await setPauseFlags(true);
+    await expectError(
+      grant(anchor.web3.Keypair.generate().publicKey),
+      "QueryPaused",
+    );
+    const consent = pda(
+      Buffer.from("eligibility_consent"),
+      owner.publicKey.toBuffer(),
+      requester.toBuffer(),
+    );
+    await program.methods
+      .revokeEligibilityConsent()
+      .accountsPartial({ user: owner.publicKey, eligibilityConsent: consent })
+      .signers([owner])
+      .rpc({ commitment: "confirmed" });
+    expect(
+      await provider.connection.getAccountInfo(consent, "confirmed"),
+    ).to.equal(null);
+    await setPauseFlags(false);
+  });
 });
 
 async function getMXEPublicKeyWithRetry(